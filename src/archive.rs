@@ -0,0 +1,114 @@
+use crate::heading::{parse_heading, TodoKeywords};
+use crate::levels::promote_to_top;
+use crate::outline::{ancestors, heading_spans};
+use crate::section::{insert_properties, planning_timestamp, push_group};
+use chrono::NaiveDate;
+
+#[derive(Debug, Clone)]
+pub struct ArchiveOptions {
+    /// Only archive entries closed at least this many days before `today`.
+    /// Entries without a `CLOSED` timestamp are then kept.
+    pub older_than_days: Option<i64>,
+    pub today: NaiveDate,
+    /// Value of `ARCHIVE_TIME`, e.g. `2024-05-01 Wed 10:00`.
+    pub archive_time: String,
+    /// Value of `ARCHIVE_FILE`: the path of the document being archived from.
+    pub source_file: String,
+    /// Value of `ARCHIVE_CATEGORY`.
+    pub category: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveResult {
+    /// The document without the archived subtrees.
+    pub remaining: String,
+    /// The archived subtrees, promoted to level 1 and annotated with
+    /// `ARCHIVE_*` properties, ready to be appended to the archive file.
+    pub archived: String,
+    pub count: usize,
+}
+
+/// The header Emacs writes at the top of a new archive file.
+pub fn archive_file_header(source_file: &str) -> String {
+    format!("#    -*- mode: org -*-\n\n\nArchived entries from file {source_file}\n\n\n")
+}
+
+/// Done states of a document without `#+TODO` lines. `CANCELLED` is not an
+/// org default, but it is what most configurations use for it.
+const DEFAULT_DONE: [&str; 2] = ["DONE", "CANCELLED"];
+
+/// Cuts every subtree whose TODO state is a done keyword (per `#+TODO`, or
+/// `DONE` and `CANCELLED` by default) out of `input`, like
+/// `org-archive-subtree`.
+///
+/// Subtrees are moved whole, so a done entry nested in another archived
+/// entry travels with its parent. Everything that is not archived is kept
+/// byte for byte.
+pub fn archive_done(
+    input: &str,
+    options: &ArchiveOptions,
+) -> Result<ArchiveResult, Box<dyn std::error::Error>> {
+    let mut keywords = TodoKeywords::from_document(input);
+    if !TodoKeywords::declared_in(input) {
+        keywords.done = DEFAULT_DONE.map(String::from).to_vec();
+    }
+    let spans = heading_spans(input);
+
+    let mut remaining = String::with_capacity(input.len());
+    let mut archived = String::new();
+    let mut count = 0;
+    let mut kept_until = 0;
+
+    for (index, span) in spans.iter().enumerate() {
+        if span.start < kept_until {
+            continue;
+        }
+        let subtree = &input[span.start..span.end];
+        let Some(heading) = parse_heading(&input[span.start..span.body_start], &keywords) else {
+            continue;
+        };
+        let Some(keyword) = heading.keyword.filter(|k| keywords.is_done(k)) else {
+            continue;
+        };
+        if let Some(days) = options.older_than_days {
+            let closed = planning_timestamp(subtree, "CLOSED")
+                .and_then(|ts| NaiveDate::parse_from_str(ts.get(1..11)?, "%Y-%m-%d").ok());
+            match closed {
+                Some(date) if (options.today - date).num_days() >= days => {}
+                _ => continue,
+            }
+        }
+
+        let olpath: Vec<String> = ancestors(&spans, index)
+            .into_iter()
+            .filter_map(|i| parse_heading(&input[spans[i].start..spans[i].body_start], &keywords))
+            .map(|h| h.title)
+            .collect();
+        let mut properties = vec![
+            ("ARCHIVE_TIME", options.archive_time.clone()),
+            ("ARCHIVE_FILE", options.source_file.clone()),
+        ];
+        if !olpath.is_empty() {
+            properties.push(("ARCHIVE_OLPATH", olpath.join("/")));
+        }
+        properties.push(("ARCHIVE_CATEGORY", options.category.clone()));
+        properties.push(("ARCHIVE_TODO", keyword));
+
+        let entry = insert_properties(&promote_to_top(subtree)?, &properties);
+        push_group(&mut archived, &entry);
+
+        remaining.push_str(&input[kept_until..span.start]);
+        kept_until = span.end;
+        count += 1;
+    }
+    remaining.push_str(&input[kept_until..]);
+
+    if !archived.is_empty() && !archived.ends_with('\n') {
+        archived.push('\n');
+    }
+    Ok(ArchiveResult {
+        remaining,
+        archived,
+        count,
+    })
+}
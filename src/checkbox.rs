@@ -0,0 +1,239 @@
+use crate::heading::{heading_level, parse_heading, BlockTracker, TodoKeywords};
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+/// Completion counts for `- [ ]`/`- [X]` checkboxes. Partially checked
+/// `[-]` items count towards the total but not as checked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CheckboxStats {
+    pub checked: usize,
+    pub total: usize,
+}
+
+impl CheckboxStats {
+    pub fn is_complete(&self) -> bool {
+        self.checked == self.total
+    }
+
+    pub fn percent(&self) -> usize {
+        (self.checked * 100).checked_div(self.total).unwrap_or(0)
+    }
+
+    fn add(&mut self, checked: bool) {
+        self.total += 1;
+        if checked {
+            self.checked += 1;
+        }
+    }
+}
+
+struct ListItem {
+    indent: usize,
+    checkbox: Option<bool>,
+}
+
+fn list_item_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^(?P<indent>[ \t]*)(?P<bullet>[-+*]|[0-9]+[.)])(?:[ \t]+\[(?P<state>[ xX-])\])?(?:[ \t]|$)",
+        )
+        .unwrap()
+    })
+}
+
+fn cookie_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\[(?:[0-9]*%|[0-9]*/[0-9]*)\]").unwrap())
+}
+
+fn parse_list_item(line: &str) -> Option<ListItem> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let caps = list_item_re().captures(line)?;
+    let indent = caps["indent"].len();
+    // An unindented `*` is a headline, not a bullet.
+    if indent == 0 && &caps["bullet"] == "*" {
+        return None;
+    }
+    Some(ListItem {
+        indent,
+        checkbox: caps
+            .name("state")
+            .map(|state| matches!(state.as_str(), "x" | "X")),
+    })
+}
+
+fn count_checkboxes<'a>(lines: impl Iterator<Item = &'a str>) -> CheckboxStats {
+    let mut stats = CheckboxStats::default();
+    let mut blocks = BlockTracker::default();
+    for line in lines {
+        if blocks.in_block(line) {
+            continue;
+        }
+        if let Some(checked) = parse_list_item(line).and_then(|item| item.checkbox) {
+            stats.add(checked);
+        }
+    }
+    stats
+}
+
+/// Counts the checkboxes in the body of the group's first section, stopping
+/// at the first child headline.
+pub fn section_checkbox_stats(group: &str) -> CheckboxStats {
+    let mut lines = group.split_inclusive('\n').peekable();
+    if lines
+        .peek()
+        .is_some_and(|line| heading_level(line).is_some())
+    {
+        lines.next();
+    }
+    count_checkboxes(lines.take_while(|line| heading_level(line).is_none()))
+}
+
+/// Counts every checkbox in the group, including those under child headlines.
+pub fn subtree_checkbox_stats(group: &str) -> CheckboxStats {
+    count_checkboxes(group.split_inclusive('\n'))
+}
+
+pub fn has_incomplete_checkboxes(group: &str) -> bool {
+    !subtree_checkbox_stats(group).is_complete()
+}
+
+/// Recomputes every `[n/m]` and `[p%]` statistics cookie in the document.
+///
+/// Like Emacs, a headline cookie counts the top-level checkboxes of its
+/// section body, or its direct TODO children when the body has no
+/// checkboxes; a list item cookie counts the item's direct child checkboxes.
+pub fn update_cookies(input: &str) -> String {
+    let keywords = TodoKeywords::from_document(input);
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+
+    let mut blocks = BlockTracker::default();
+    let in_block: Vec<bool> = lines.iter().map(|line| blocks.in_block(line)).collect();
+    let levels: Vec<Option<usize>> = lines
+        .iter()
+        .zip(&in_block)
+        .map(|(line, &block)| if block { None } else { heading_level(line) })
+        .collect();
+    let items: Vec<Option<ListItem>> = lines
+        .iter()
+        .zip(&levels)
+        .zip(&in_block)
+        .map(|((line, level), &block)| {
+            if block || level.is_some() {
+                None
+            } else {
+                parse_list_item(line)
+            }
+        })
+        .collect();
+
+    let mut output = String::with_capacity(input.len());
+    for (i, line) in lines.iter().enumerate() {
+        if in_block[i] || !cookie_re().is_match(line) {
+            output.push_str(line);
+            continue;
+        }
+
+        let stats = if let Some(level) = levels[i] {
+            Some(headline_stats(i, level, &lines, &levels, &items, &keywords))
+        } else {
+            items[i]
+                .as_ref()
+                .map(|item| list_item_stats(i, item.indent, &lines, &levels, &items))
+        };
+
+        match stats {
+            Some(stats) => output.push_str(&replace_cookies(line, stats)),
+            None => output.push_str(line),
+        }
+    }
+    output
+}
+
+fn headline_stats(
+    index: usize,
+    level: usize,
+    lines: &[&str],
+    levels: &[Option<usize>],
+    items: &[Option<ListItem>],
+    keywords: &TodoKeywords,
+) -> CheckboxStats {
+    let body_end = (index + 1..lines.len())
+        .find(|&j| levels[j].is_some())
+        .unwrap_or(lines.len());
+
+    let body_items: Vec<&ListItem> = items[index + 1..body_end].iter().flatten().collect();
+    if let Some(top) = body_items.iter().map(|item| item.indent).min() {
+        let mut stats = CheckboxStats::default();
+        for item in body_items.iter().filter(|item| item.indent == top) {
+            if let Some(checked) = item.checkbox {
+                stats.add(checked);
+            }
+        }
+        if stats.total > 0 {
+            return stats;
+        }
+    }
+
+    let mut stats = CheckboxStats::default();
+    let mut child_level = None;
+    for j in body_end..lines.len() {
+        let Some(child) = levels[j] else { continue };
+        if child <= level {
+            break;
+        }
+        let direct = *child_level.get_or_insert(child);
+        if child != direct {
+            continue;
+        }
+        if let Some(keyword) = parse_heading(lines[j], keywords).and_then(|h| h.keyword) {
+            stats.add(keywords.is_done(&keyword));
+        }
+    }
+    stats
+}
+
+fn list_item_stats(
+    index: usize,
+    indent: usize,
+    lines: &[&str],
+    levels: &[Option<usize>],
+    items: &[Option<ListItem>],
+) -> CheckboxStats {
+    let mut stats = CheckboxStats::default();
+    let mut child_indent = None;
+    for j in index + 1..lines.len() {
+        if levels[j].is_some() {
+            break;
+        }
+        let line = lines[j];
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line_indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+        if line_indent <= indent {
+            break;
+        }
+        let Some(item) = &items[j] else { continue };
+        let direct = *child_indent.get_or_insert(item.indent);
+        if item.indent == direct {
+            if let Some(checked) = item.checkbox {
+                stats.add(checked);
+            }
+        }
+    }
+    stats
+}
+
+fn replace_cookies(line: &str, stats: CheckboxStats) -> String {
+    cookie_re()
+        .replace_all(line, |caps: &Captures| {
+            if caps[0].ends_with("%]") {
+                format!("[{}%]", stats.percent())
+            } else {
+                format!("[{}/{}]", stats.checked, stats.total)
+            }
+        })
+        .into_owned()
+}
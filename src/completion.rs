@@ -0,0 +1,50 @@
+use crate::heading::{parse_heading, TodoKeywords};
+use crate::outline::{heading_spans, parent_indices};
+
+/// Values of a document that shell completion can offer: heading paths for
+/// `refile --to`, and tags and TODO keywords for the options that match
+/// headlines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompletionValues {
+    /// Every heading path, in the `/`-separated form of `refile --to`.
+    pub heading_paths: Vec<String>,
+    /// Headline tags, sorted and without duplicates.
+    pub tags: Vec<String>,
+    /// The TODO keywords of the document, in `#+TODO` order.
+    pub keywords: Vec<String>,
+}
+
+impl CompletionValues {
+    pub fn from_document(input: &str) -> Self {
+        let keywords = TodoKeywords::from_document(input);
+        let spans = heading_spans(input);
+        let parents = parent_indices(&spans);
+
+        let mut values = CompletionValues {
+            keywords: keywords.active.iter().chain(&keywords.done).cloned().collect(),
+            ..CompletionValues::default()
+        };
+        let mut paths: Vec<Option<String>> = Vec::with_capacity(spans.len());
+        for (index, span) in spans.iter().enumerate() {
+            let Some(heading) = parse_heading(&input[span.start..span.body_start], &keywords) else {
+                paths.push(None);
+                continue;
+            };
+            // A title containing `/` cannot be named by a path, nor can
+            // anything below it.
+            let path = match parents[index] {
+                _ if heading.title.contains('/') => None,
+                Some(parent) => paths[parent].as_ref().map(|p| format!("{p}/{}", heading.title)),
+                None => Some(heading.title),
+            };
+            if let Some(path) = path.as_ref().filter(|p| !values.heading_paths.contains(p)) {
+                values.heading_paths.push(path.clone());
+            }
+            paths.push(path);
+            values.tags.extend(heading.tags);
+        }
+        values.tags.sort();
+        values.tags.dedup();
+        values
+    }
+}
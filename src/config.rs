@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// File name of the project-local config, looked up from the current
+/// directory upwards.
+pub const PROJECT_CONFIG: &str = ".org_grouper.toml";
+
+/// Options that run commands or write files. A project config comes with
+/// whatever directory the user happens to be in, so it may not set these.
+pub const USER_ONLY_DEFAULTS: [&str; 5] = ["sh", "shell", "split-into", "archive-file", "target-file"];
+
+/// Default options and named aliases from config files:
+///
+/// ```toml
+/// [defaults]
+/// group-headings-at = 2
+/// out-replace-nulls-with = '\n---\n'
+///
+/// [aliases]
+/// todos = ["--group-headings-at=2", "ugrep", "--null-data", "-i", "TODO"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Default options, as the arguments they stand for.
+    pub defaults: Vec<(String, Vec<String>)>,
+    /// Alias names, without the `@`, and the arguments they expand to.
+    pub aliases: BTreeMap<String, Vec<String>>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let table: Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        let mut config = Config::default();
+
+        for (section, value) in table {
+            let Value::Table(entries) = value else {
+                return Err(format!("'{section}' must be a table"));
+            };
+            match section.as_str() {
+                "defaults" => {
+                    for (name, value) in entries {
+                        let args = option_args(&name, &value)?;
+                        config.defaults.push((name, args));
+                    }
+                }
+                "aliases" => {
+                    for (name, value) in entries {
+                        let args = string_array(&value)
+                            .ok_or_else(|| format!("Alias '{name}' must be an array of strings"))?;
+                        config.aliases.insert(name, args);
+                    }
+                }
+                _ => return Err(format!("Unknown section '[{section}]'")),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Layers `other` over this config: its defaults and aliases win.
+    pub fn merge(&mut self, other: Config) {
+        for (name, args) in other.defaults {
+            self.defaults.retain(|(existing, _)| *existing != name);
+            self.defaults.push((name, args));
+        }
+        self.aliases.extend(other.aliases);
+    }
+
+    /// All default options, as arguments to put before the user's own.
+    pub fn default_args(&self) -> Vec<String> {
+        self.defaults.iter().flat_map(|(_, args)| args.clone()).collect()
+    }
+
+    /// Replaces a leading `@NAME` in `args` (after the program name) with
+    /// the arguments of alias `NAME`.
+    pub fn expand_alias(&self, args: Vec<OsString>) -> Result<Vec<OsString>, String> {
+        let Some(name) = args
+            .get(1)
+            .and_then(|arg| arg.to_str())
+            .and_then(|arg| arg.strip_prefix('@'))
+        else {
+            return Ok(args);
+        };
+        let expansion = self
+            .aliases
+            .get(name)
+            .ok_or_else(|| format!("Unknown alias '@{name}'"))?;
+
+        let mut expanded = Vec::with_capacity(args.len() + expansion.len());
+        expanded.push(args[0].clone());
+        expanded.extend(expansion.iter().map(OsString::from));
+        expanded.extend(args.into_iter().skip(2));
+        Ok(expanded)
+    }
+}
+
+fn string_array(value: &Value) -> Option<Vec<String>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|item| item.as_str().map(str::to_string))
+        .collect()
+}
+
+/// The arguments a `[defaults]` entry stands for: `--name=value`, `--name`
+/// for `true`, nothing for `false`, and one `--name=value` per item of an
+/// array.
+fn option_args(name: &str, value: &Value) -> Result<Vec<String>, String> {
+    let scalar = |value: &Value| match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Integer(n) => Some(n.to_string()),
+        Value::Float(n) => Some(n.to_string()),
+        _ => None,
+    };
+    match value {
+        Value::Boolean(true) => Ok(vec![format!("--{name}")]),
+        Value::Boolean(false) => Ok(Vec::new()),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| scalar(item).map(|value| format!("--{name}={value}")))
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("Default '{name}' must hold strings or numbers")),
+        _ => scalar(value)
+            .map(|value| vec![format!("--{name}={value}")])
+            .ok_or_else(|| format!("Default '{name}' must be a string, number, boolean or array")),
+    }
+}
+
+/// The config files that apply in `cwd`, lowest precedence first: the user
+/// config in `$XDG_CONFIG_HOME/org_grouper/config.toml` (or
+/// `~/.config/...`), then the nearest `.org_grouper.toml`.
+pub fn config_paths(cwd: &Path, xdg_config_home: Option<PathBuf>, home: Option<PathBuf>) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let config_home = xdg_config_home
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home.map(|home| home.join(".config")));
+    if let Some(dir) = config_home {
+        paths.push(dir.join("org_grouper").join("config.toml"));
+    }
+    if let Some(dir) = cwd.ancestors().find(|dir| dir.join(PROJECT_CONFIG).is_file()) {
+        paths.push(dir.join(PROJECT_CONFIG));
+    }
+    paths
+}
+
+/// Reads and merges the config files among `paths` that exist.
+pub fn load_config(paths: &[PathBuf]) -> Result<Config, String> {
+    let mut config = Config::default();
+    for path in paths {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        };
+        let file = Config::parse(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        if path.file_name().is_some_and(|name| name == PROJECT_CONFIG) {
+            let user_only = file
+                .defaults
+                .iter()
+                .find(|(name, _)| USER_ONLY_DEFAULTS.contains(&name.as_str()));
+            if let Some((name, _)) = user_only {
+                return Err(format!(
+                    "{}: '{name}' runs commands or writes files, so only the user config can set it",
+                    path.display()
+                ));
+            }
+            // Nor may it change what an alias the user already relies on runs.
+            if let Some(name) = file.aliases.keys().find(|name| config.aliases.contains_key(*name)) {
+                return Err(format!(
+                    "{}: alias '{name}' is already defined in the user config",
+                    path.display()
+                ));
+            }
+        }
+        config.merge(file);
+    }
+    Ok(config)
+}
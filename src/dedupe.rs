@@ -0,0 +1,112 @@
+use crate::group_org_sections;
+use regex::Regex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// How two groups are compared when looking for duplicates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupeMode {
+    /// Byte-identical groups.
+    Exact,
+    /// Groups with the same headline line.
+    Heading,
+    /// Groups that are equal after collapsing whitespace and blanking out
+    /// timestamps, checkbox states and statistics cookies.
+    Normalized,
+}
+
+impl FromStr for DedupeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exact" => Ok(DedupeMode::Exact),
+            "heading" => Ok(DedupeMode::Heading),
+            "normalized" => Ok(DedupeMode::Normalized),
+            _ => Err(format!(
+                "Invalid dedupe mode '{s}' (expected exact, heading or normalized)"
+            )),
+        }
+    }
+}
+
+fn volatile_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"[<\[]\d{4}-\d{2}-\d{2}[^>\]\n]*[>\]]|\[[ xX-]\]|\[(?:[0-9]*%|[0-9]*/[0-9]*)\]")
+            .unwrap()
+    })
+}
+
+/// The text a group is compared by under `mode`.
+fn comparison_key(group: &str, mode: DedupeMode) -> String {
+    match mode {
+        DedupeMode::Exact => group.to_string(),
+        DedupeMode::Heading => group.lines().next().unwrap_or("").trim_end().to_string(),
+        DedupeMode::Normalized => {
+            let blanked = volatile_re().replace_all(group, " ");
+            blanked.split_whitespace().collect::<Vec<_>>().join(" ")
+        }
+    }
+}
+
+pub fn content_hash(group: &str, mode: DedupeMode) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    comparison_key(group, mode).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns clusters of indices of groups that are duplicates of each other,
+/// in order of first occurrence. Groups without a duplicate are omitted.
+pub fn duplicate_clusters<S: AsRef<str>>(groups: &[S], mode: DedupeMode) -> Vec<Vec<usize>> {
+    let mut clusters: Vec<(String, Vec<usize>)> = Vec::new();
+    let mut by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+
+    for (index, group) in groups.iter().enumerate() {
+        let group = group.as_ref();
+        let key = comparison_key(group, mode);
+        let candidates = by_hash.entry(content_hash(group, mode)).or_default();
+        // Hashes only narrow down the candidates; keys decide equality.
+        match candidates.iter().find(|&&c| clusters[c].0 == key) {
+            Some(&c) => clusters[c].1.push(index),
+            None => {
+                candidates.push(clusters.len());
+                clusters.push((key, vec![index]));
+            }
+        }
+    }
+
+    clusters
+        .into_iter()
+        .map(|(_, indices)| indices)
+        .filter(|indices| indices.len() > 1)
+        .collect()
+}
+
+/// Drops every group that duplicates an earlier one, keeping first occurrences.
+pub fn dedupe_groups(groups: Vec<String>, mode: DedupeMode) -> Vec<String> {
+    let mut drop = vec![false; groups.len()];
+    for cluster in duplicate_clusters(&groups, mode) {
+        for &index in &cluster[1..] {
+            drop[index] = true;
+        }
+    }
+    groups
+        .into_iter()
+        .zip(drop)
+        .filter_map(|(group, dropped)| (!dropped).then_some(group))
+        .collect()
+}
+
+/// Groups `input` at `level` and removes duplicated groups.
+pub fn dedupe_sections(
+    input: &str,
+    level: usize,
+    mode: DedupeMode,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let groups = group_org_sections(input, level)?;
+    Ok(dedupe_groups(groups, mode).concat())
+}
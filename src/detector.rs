@@ -0,0 +1,326 @@
+use crate::heading::heading_level;
+use regex::Regex;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Decides which lines of a document start a heading, and at what level.
+pub trait HeadingDetector {
+    /// Returns the level of the heading that starts at `line`, if any.
+    /// `following` are the lines after it, for headings underlined on the
+    /// lines below. Called once for every line, in document order, so that
+    /// detectors can keep track of code blocks.
+    fn heading_level(&mut self, line: &str, following: &[&str]) -> Option<usize>;
+}
+
+/// Org headlines: `*` stars followed by whitespace.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OrgHeadings;
+
+impl HeadingDetector for OrgHeadings {
+    fn heading_level(&mut self, line: &str, _following: &[&str]) -> Option<usize> {
+        heading_level(line)
+    }
+}
+
+/// CommonMark headings: ATX (`#` to `######`) and setext (a line
+/// underlined with `=` for level 1 or `-` for level 2). Lines inside
+/// fenced code blocks are never headings.
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownHeadings {
+    /// The fence character and length of the open code block.
+    fence: Option<(char, usize)>,
+    /// Whether the previous line was the title of a setext heading, making
+    /// this line its underline.
+    underline: bool,
+}
+
+impl HeadingDetector for MarkdownHeadings {
+    fn heading_level(&mut self, line: &str, following: &[&str]) -> Option<usize> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        if let Some((fence_char, fence_len)) = self.fence {
+            if let Some((c, len, info)) = fence(line) {
+                if c == fence_char && len >= fence_len && info.trim().is_empty() {
+                    self.fence = None;
+                }
+            }
+            return None;
+        }
+        if let Some((c, len, info)) = fence(line) {
+            // A backtick fence's info string cannot contain backticks.
+            if c == '~' || !info.contains('`') {
+                self.fence = Some((c, len));
+                self.underline = false;
+                return None;
+            }
+        }
+        if std::mem::take(&mut self.underline) {
+            return None;
+        }
+
+        if let Some(caps) = atx_re().captures(line) {
+            return Some(caps["hashes"].len());
+        }
+        let level = following
+            .first()
+            .and_then(|next| setext_re().captures(next.trim_end_matches(['\n', '\r'])))
+            .filter(|_| is_paragraph_line(line))
+            .map(|caps| if caps["underline"].starts_with('=') { 1 } else { 2 });
+        self.underline = level.is_some();
+        level
+    }
+}
+
+/// Splits an opening or closing code fence into its character, length and
+/// info string.
+fn fence(line: &str) -> Option<(char, usize, &str)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let rest = line.get(indent..).filter(|_| indent < 4)?;
+    let c = rest.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = rest.chars().take_while(|&x| x == c).count();
+    (len >= 3).then(|| (c, len, &rest[len..]))
+}
+
+/// Whether `line` can be the title of a setext heading: text that is not
+/// blank, indented code, another heading, a list item or a block quote.
+fn is_paragraph_line(line: &str) -> bool {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let text = line.trim();
+    indent < 4
+        && !text.is_empty()
+        && !atx_re().is_match(line)
+        && !list_item_re().is_match(text)
+        && !text.starts_with('>')
+}
+
+fn atx_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^ {0,3}(?P<hashes>#{1,6})(?:[ \t]|$)").unwrap())
+}
+
+fn setext_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^ {0,3}(?P<underline>=+|-+)[ \t]*$").unwrap())
+}
+
+fn list_item_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?:[-+*]|\d{1,9}[.)])(?:[ \t]|$)").unwrap())
+}
+
+/// reStructuredText section titles: a line underlined, and optionally
+/// overlined, with a repeated punctuation character. As in docutils, each
+/// adornment style gets the next level the first time it appears.
+#[derive(Debug, Clone, Default)]
+pub struct RstHeadings {
+    /// Adornment styles in order of appearance: the character and whether
+    /// it has an overline.
+    styles: Vec<(char, bool)>,
+    /// Lines still to skip: the title and underline of a heading.
+    skip: usize,
+}
+
+impl HeadingDetector for RstHeadings {
+    fn heading_level(&mut self, line: &str, following: &[&str]) -> Option<usize> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        if self.skip > 0 {
+            self.skip -= 1;
+            return None;
+        }
+        let next = following.first()?.trim_end_matches(['\n', '\r']);
+        let title_width = |title: &str| title.trim_end().chars().count();
+
+        let style = match (adornment(line), adornment(next)) {
+            // The overline must be at least as long as the title, and the
+            // underline must repeat it.
+            (Some((c, len)), None)
+                if !next.trim().is_empty()
+                    && title_width(next.trim_start()) <= len
+                    && following.get(1).and_then(|underline| adornment(underline)) == Some((c, len)) =>
+            {
+                self.skip = 2;
+                (c, true)
+            }
+            (None, Some((c, len)))
+                if !line.starts_with([' ', '\t']) && !line.trim().is_empty() && title_width(line) <= len =>
+            {
+                self.skip = 1;
+                (c, false)
+            }
+            _ => return None,
+        };
+        let index = match self.styles.iter().position(|known| *known == style) {
+            Some(index) => index,
+            None => {
+                self.styles.push(style);
+                self.styles.len() - 1
+            }
+        };
+        Some(index + 1)
+    }
+}
+
+/// The character and length of a line made of one repeated punctuation
+/// character.
+fn adornment(line: &str) -> Option<(char, usize)> {
+    let line = line.trim_end();
+    let c = line.chars().next().filter(|c| c.is_ascii_punctuation())?;
+    line.chars().all(|x| x == c).then_some((c, line.len()))
+}
+
+/// AsciiDoc section titles: `= Document title` at level 0, `== Section` at
+/// level 1, down to `======` at level 5. Lines inside delimited blocks,
+/// such as `----` listings, are never titles.
+#[derive(Debug, Clone, Default)]
+pub struct AsciiDocHeadings {
+    /// The delimiter line of the open block.
+    block: Option<String>,
+}
+
+impl HeadingDetector for AsciiDocHeadings {
+    fn heading_level(&mut self, line: &str, _following: &[&str]) -> Option<usize> {
+        let line = line.trim_end();
+        if asciidoc_delimiter_re().is_match(line) {
+            match &self.block {
+                Some(open) if open == line => self.block = None,
+                Some(_) => {}
+                None => self.block = Some(line.to_string()),
+            }
+            return None;
+        }
+        if self.block.is_some() {
+            return None;
+        }
+        asciidoc_title_re()
+            .captures(line)
+            .map(|caps| caps["equals"].len() - 1)
+    }
+}
+
+fn asciidoc_title_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?P<equals>={1,6})[ \t]+\S").unwrap())
+}
+
+fn asciidoc_delimiter_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?:-{4,}|\.{4,}|\+{4,}|_{4,}|={4,}|\*{4,}|/{4,}|`{3,})$").unwrap())
+}
+
+/// Emacs `outline-mode` headings in Lisp files: `;;; ` at level 1, `;;;; `
+/// at level 2, and so on. `;;;###autoload` cookies are not headings.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutlineHeadings;
+
+impl HeadingDetector for OutlineHeadings {
+    fn heading_level(&mut self, line: &str, _following: &[&str]) -> Option<usize> {
+        outline_re()
+            .captures(line)
+            .map(|caps| caps["semicolons"].len() - 2)
+    }
+}
+
+fn outline_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?P<semicolons>;{3,})[ \t]+\S").unwrap())
+}
+
+/// Headings matched by a user-supplied regex with a `level` capture, holding
+/// the level as a number, or a `stars` capture, whose length in characters
+/// is the level.
+#[derive(Debug, Clone)]
+pub struct RegexHeadings {
+    regex: Regex,
+}
+
+impl RegexHeadings {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+        if !regex.capture_names().flatten().any(|name| name == "level" || name == "stars") {
+            return Err(format!(
+                "The heading regex '{pattern}' needs a capture group named 'level' or 'stars'"
+            ));
+        }
+        Ok(RegexHeadings { regex })
+    }
+}
+
+impl HeadingDetector for RegexHeadings {
+    fn heading_level(&mut self, line: &str, _following: &[&str]) -> Option<usize> {
+        let caps = self.regex.captures(line.trim_end_matches(['\n', '\r']))?;
+        if let Some(level) = caps.name("level") {
+            return level.as_str().trim().parse().ok();
+        }
+        caps.name("stars").map(|stars| stars.as_str().chars().count())
+    }
+}
+
+/// The syntax of the input document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputFormat {
+    #[default]
+    Org,
+    Markdown,
+    Rst,
+    AsciiDoc,
+    /// Emacs `outline-mode` comments in Lisp files.
+    Outline,
+}
+
+impl InputFormat {
+    pub fn detector(self) -> Box<dyn HeadingDetector> {
+        match self {
+            InputFormat::Org => Box::new(OrgHeadings),
+            InputFormat::Markdown => Box::new(MarkdownHeadings::default()),
+            InputFormat::Rst => Box::new(RstHeadings::default()),
+            InputFormat::AsciiDoc => Box::new(AsciiDocHeadings::default()),
+            InputFormat::Outline => Box::new(OutlineHeadings),
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "org" => Ok(InputFormat::Org),
+            "markdown" | "md" => Ok(InputFormat::Markdown),
+            "rst" => Ok(InputFormat::Rst),
+            "asciidoc" | "adoc" => Ok(InputFormat::AsciiDoc),
+            "outline" => Ok(InputFormat::Outline),
+            _ => Err(format!(
+                "Unknown input format '{s}' (expected org, markdown, rst, asciidoc or outline)"
+            )),
+        }
+    }
+}
+
+/// Whether `group` starts with a heading, rather than being the text
+/// before the first one.
+pub fn starts_with_heading(group: &str, detector: &mut dyn HeadingDetector) -> bool {
+    let lines: Vec<&str> = group.split_inclusive('\n').collect();
+    lines
+        .first()
+        .is_some_and(|first| detector.heading_level(first, &lines[1..]).is_some())
+}
+
+/// Splits `input` into groups like [`crate::group_org_sections`], with the
+/// headings found by `detector`.
+pub fn group_sections(input: &str, level: usize, detector: &mut dyn HeadingDetector) -> Vec<String> {
+    let mut groups: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+
+    for (index, line) in lines.iter().enumerate() {
+        let heading = detector.heading_level(line, &lines[index + 1..]);
+        if heading.is_some_and(|heading| heading <= level) && !current.is_empty() {
+            groups.push(std::mem::take(&mut current));
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        groups.push(current);
+    }
+
+    groups
+}
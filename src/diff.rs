@@ -0,0 +1,14 @@
+use similar::TextDiff;
+
+/// Renders a unified diff of `old` against `new` with three lines of
+/// context, like `diff -u`. Returns an empty string when they are equal.
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(old_name, new_name)
+        .to_string()
+}
@@ -0,0 +1,593 @@
+use crate::heading::{heading_level, parse_heading, starts_with_ignore_case, TodoKeywords};
+use crate::html::to_html;
+use crate::markdown::to_markdown;
+use regex::Regex;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Output format of exported groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// The org text itself.
+    #[default]
+    Org,
+    /// CommonMark, with GitHub's tables and strikethrough.
+    Markdown,
+    /// A standalone HTML page.
+    Html,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "org" => Ok(ExportFormat::Org),
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "html" => Ok(ExportFormat::Html),
+            _ => Err(format!("Unknown export format '{s}' (expected org, markdown or html)")),
+        }
+    }
+}
+
+/// What becomes of `:PROPERTIES:` drawers on export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PropertyMode {
+    #[default]
+    Drop,
+    /// The first drawer, with `#+TITLE`, `#+AUTHOR` and the like, goes into
+    /// YAML front matter; the others are dropped.
+    FrontMatter,
+}
+
+impl FromStr for PropertyMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop" => Ok(PropertyMode::Drop),
+            "front-matter" => Ok(PropertyMode::FrontMatter),
+            _ => Err(format!("Unknown property mode '{s}' (expected drop or front-matter)")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportOptions {
+    pub properties: PropertyMode,
+    /// The title of the whole document, for a page exported from a group
+    /// without its own `#+TITLE`.
+    pub title: Option<String>,
+}
+
+/// Converts org `input` to `format`. `keywords` are the TODO keywords of the
+/// whole document, which a single group may not define itself.
+pub fn export(input: &str, format: ExportFormat, keywords: &TodoKeywords, options: &ExportOptions) -> String {
+    match format {
+        ExportFormat::Org => input.to_string(),
+        ExportFormat::Markdown => to_markdown(&parse_document(input, keywords), options),
+        ExportFormat::Html => to_html(&parse_document(input, keywords), options),
+    }
+}
+
+/// Org text markup, e.g. `*bold*` or `[[https://orgmode.org][links]]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inline {
+    Text(String),
+    Bold(Vec<Inline>),
+    Italic(Vec<Inline>),
+    Underline(Vec<Inline>),
+    Strike(Vec<Inline>),
+    /// `=verbatim=` and `~code~`.
+    Code(String),
+    Link {
+        target: String,
+        description: Option<Vec<Inline>>,
+    },
+    /// `\\` at the end of a line.
+    LineBreak,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checkbox {
+    Unchecked,
+    Partial,
+    Checked,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListItem {
+    pub checkbox: Option<Checkbox>,
+    /// The term of a `- term :: description` item.
+    pub term: Option<Vec<Inline>>,
+    pub content: Vec<Block>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    Heading {
+        level: usize,
+        keyword: Option<String>,
+        done: bool,
+        priority: Option<char>,
+        title: Vec<Inline>,
+        tags: Vec<String>,
+        properties: Vec<(String, String)>,
+    },
+    Paragraph(Vec<Inline>),
+    List {
+        ordered: bool,
+        items: Vec<ListItem>,
+    },
+    Table {
+        /// Whether a rule separates the first row from the others.
+        header: bool,
+        rows: Vec<Vec<Vec<Inline>>>,
+    },
+    /// Src and example blocks, and `: ` fixed-width lines.
+    Code {
+        language: Option<String>,
+        code: String,
+    },
+    Quote(Vec<Block>),
+    /// The contents of a `#+BEGIN_EXPORT FORMAT` block.
+    Raw {
+        format: String,
+        text: String,
+    },
+    Rule,
+}
+
+/// Keywords that describe the document in front matter.
+const FRONT_MATTER_KEYWORDS: [&str; 6] = ["TITLE", "SUBTITLE", "AUTHOR", "EMAIL", "DATE", "DESCRIPTION"];
+
+/// An org document, as far as export is concerned.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    /// `#+KEY: value` lines, keys in upper case.
+    pub keywords: Vec<(String, String)>,
+    /// The property drawer before the first headline.
+    pub properties: Vec<(String, String)>,
+    pub blocks: Vec<Block>,
+}
+
+impl Document {
+    pub fn keyword(&self, key: &str) -> Option<&str> {
+        self.keywords
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The entries of front matter, with lower-case keys: the keywords
+    /// that describe the document, then the front properties.
+    pub fn front_matter(&self) -> Vec<(String, &str)> {
+        let keywords = FRONT_MATTER_KEYWORDS
+            .iter()
+            .filter_map(|key| self.keyword(key).map(|value| (key.to_lowercase(), value)));
+        let properties = self
+            .front_properties()
+            .iter()
+            .map(|(key, value)| (key.to_lowercase(), value.as_str()));
+        keywords.chain(properties).collect()
+    }
+
+    /// The properties for front matter: the drawer before the first
+    /// headline, or else that of a leading headline.
+    pub fn front_properties(&self) -> &[(String, String)] {
+        match self.blocks.first() {
+            Some(Block::Heading { properties, .. }) if self.properties.is_empty() => properties,
+            _ => &self.properties,
+        }
+    }
+}
+
+pub fn parse_document(input: &str, keywords: &TodoKeywords) -> Document {
+    let lines: Vec<&str> = input.lines().collect();
+    let mut document = Document::default();
+    let blocks = parse_blocks(&lines, keywords, &mut document);
+    document.blocks = blocks;
+    document
+}
+
+fn keyword_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^[ \t]*#\+(?P<key>[A-Za-z_]+):[ \t]*(?P<value>.*)$").unwrap())
+}
+
+fn block_begin_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)^[ \t]*#\+begin_(?P<name>\S+)(?:[ \t]+(?P<params>.*))?$").unwrap())
+}
+
+fn drawer_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^[ \t]*:(?P<name>[A-Za-z0-9_-]+):[ \t]*$").unwrap())
+}
+
+fn planning_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^[ \t]*(?:SCHEDULED|DEADLINE|CLOSED):").unwrap())
+}
+
+fn list_item_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^(?P<indent>[ \t]*)(?P<bullet>[-+*]|\d+[.)])(?:[ \t]+(?P<rest>.*))?$").unwrap()
+    })
+}
+
+fn rule_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^[ \t]*-{5,}[ \t]*$").unwrap())
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn is_fixed_width(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed == ":" || trimmed.starts_with(": ")
+}
+
+fn is_comment(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed == "#" || trimmed.starts_with("# ")
+}
+
+/// The indentation of a list item line; `*` only starts an item when
+/// indented, since it would be a headline otherwise.
+fn list_item_indent(line: &str) -> Option<usize> {
+    let caps = list_item_re().captures(line)?;
+    let indent = caps["indent"].len();
+    (indent > 0 || &caps["bullet"] != "*").then_some(indent)
+}
+
+/// Whether `line` starts an element other than a paragraph.
+fn starts_element(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    heading_level(line).is_some()
+        || list_item_indent(line).is_some()
+        || trimmed.starts_with('|')
+        || trimmed.starts_with("#+")
+        || is_comment(line)
+        || is_fixed_width(line)
+        || drawer_re().is_match(line)
+        || rule_re().is_match(line)
+}
+
+/// Reads a `:NAME:` ... `:END:` drawer starting at `lines[start]`, returning
+/// its `:KEY: value` pairs and the index after it.
+fn parse_drawer(lines: &[&str], start: usize) -> (Vec<(String, String)>, usize) {
+    let mut properties = Vec::new();
+    let mut i = start + 1;
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+        i += 1;
+        if trimmed.eq_ignore_ascii_case(":END:") {
+            break;
+        }
+        if let Some((key, value)) = trimmed.strip_prefix(':').and_then(|rest| rest.split_once(':')) {
+            properties.push((key.to_string(), value.trim().to_string()));
+        }
+    }
+    (properties, i)
+}
+
+fn parse_blocks(lines: &[&str], keywords: &TodoKeywords, document: &mut Document) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut seen_heading = false;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || is_comment(line) {
+            i += 1;
+        } else if let Some(heading) = parse_heading(line, keywords) {
+            seen_heading = true;
+            i += 1;
+            while i < lines.len() && planning_re().is_match(lines[i]) {
+                i += 1;
+            }
+            let mut properties = Vec::new();
+            if i < lines.len() && lines[i].trim().eq_ignore_ascii_case(":PROPERTIES:") {
+                (properties, i) = parse_drawer(lines, i);
+            }
+            blocks.push(Block::Heading {
+                level: heading.level,
+                done: heading.keyword.as_deref().is_some_and(|k| keywords.is_done(k)),
+                keyword: heading.keyword,
+                priority: heading.priority,
+                title: parse_inline(&heading.title),
+                tags: heading.tags,
+                properties,
+            });
+        } else if trimmed.eq_ignore_ascii_case(":END:") {
+            i += 1;
+        } else if drawer_re().is_match(line) && lines[i + 1..].iter().any(|l| l.trim().eq_ignore_ascii_case(":END:")) {
+            let (properties, next) = parse_drawer(lines, i);
+            if !seen_heading && document.properties.is_empty() && trimmed.eq_ignore_ascii_case(":PROPERTIES:") {
+                document.properties = properties;
+            }
+            i = next;
+        } else if let Some(caps) = block_begin_re().captures(line) {
+            let name = caps["name"].to_ascii_lowercase();
+            let params = caps.name("params").map_or("", |m| m.as_str()).trim();
+            let end = format!("#+end_{name}");
+            let body_start = i + 1;
+            let mut j = body_start;
+            while j < lines.len() && !starts_with_ignore_case(lines[j].trim_start(), &end) {
+                j += 1;
+            }
+            let body = &lines[body_start..j];
+            i = j + 1;
+            match name.as_str() {
+                "src" | "example" => blocks.push(Block::Code {
+                    language: params
+                        .split_whitespace()
+                        .next()
+                        .filter(|_| name == "src")
+                        .map(str::to_string),
+                    code: block_code(body),
+                }),
+                "quote" => blocks.push(Block::Quote(parse_blocks(body, keywords, document))),
+                "export" => blocks.push(Block::Raw {
+                    format: params.split_whitespace().next().unwrap_or("").to_ascii_lowercase(),
+                    text: body.iter().map(|l| format!("{l}\n")).collect(),
+                }),
+                "comment" => {}
+                _ => blocks.extend(parse_blocks(body, keywords, document)),
+            }
+        } else if let Some(caps) = keyword_re().captures(line) {
+            document
+                .keywords
+                .push((caps["key"].to_ascii_uppercase(), caps["value"].trim().to_string()));
+            i += 1;
+        } else if trimmed.starts_with("#+") {
+            i += 1;
+        } else if trimmed.starts_with('|') {
+            let mut rows = Vec::new();
+            let mut header = false;
+            while i < lines.len() && lines[i].trim_start().starts_with('|') {
+                let row = lines[i].trim();
+                i += 1;
+                if row.starts_with("|-") {
+                    header |= rows.len() == 1;
+                    continue;
+                }
+                let row = row.strip_prefix('|').unwrap_or(row);
+                let row = row.strip_suffix('|').unwrap_or(row);
+                rows.push(row.split('|').map(|cell| parse_inline(cell.trim())).collect());
+            }
+            blocks.push(Block::Table { header, rows });
+        } else if is_fixed_width(line) {
+            let mut code = String::new();
+            while i < lines.len() && is_fixed_width(lines[i]) {
+                let text = lines[i].trim_start();
+                code.push_str(text.strip_prefix(": ").unwrap_or(""));
+                code.push('\n');
+                i += 1;
+            }
+            blocks.push(Block::Code { language: None, code });
+        } else if rule_re().is_match(line) {
+            blocks.push(Block::Rule);
+            i += 1;
+        } else if let Some(indent) = list_item_indent(line) {
+            let (block, next) = parse_list(lines, i, indent, keywords, document);
+            blocks.push(block);
+            i = next;
+        } else {
+            let mut text = String::new();
+            while i < lines.len() && !lines[i].trim().is_empty() && (text.is_empty() || !starts_element(lines[i])) {
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(lines[i].trim());
+                i += 1;
+            }
+            blocks.push(Block::Paragraph(parse_inline(&text)));
+        }
+    }
+
+    blocks
+}
+
+/// The code of a src or example block, without the commas that protect
+/// lines starting with `*` or `#+`.
+fn block_code(body: &[&str]) -> String {
+    let indent = body
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| indent_of(line))
+        .min()
+        .unwrap_or(0);
+    let mut code = String::new();
+    for line in body {
+        let line = line.get(indent..).unwrap_or("");
+        let line = match line.strip_prefix(',') {
+            Some(rest) if rest.starts_with('*') || rest.starts_with("#+") || rest.starts_with(",*") => rest,
+            _ => line,
+        };
+        code.push_str(line);
+        code.push('\n');
+    }
+    code
+}
+
+/// Parses the list whose first item is `lines[start]`, at `indent`.
+fn parse_list(
+    lines: &[&str],
+    start: usize,
+    indent: usize,
+    keywords: &TodoKeywords,
+    document: &mut Document,
+) -> (Block, usize) {
+    let ordered = list_item_re().captures(lines[start]).is_some_and(|caps| caps["bullet"].ends_with(['.', ')']));
+    let mut items = Vec::new();
+    let mut i = start;
+
+    while i < lines.len() && list_item_indent(lines[i]) == Some(indent) {
+        let caps = list_item_re().captures(lines[i]).unwrap();
+        // A different kind of bullet starts another list.
+        if caps["bullet"].ends_with(['.', ')']) != ordered {
+            break;
+        }
+        let mut rest = caps.name("rest").map_or("", |m| m.as_str());
+        i += 1;
+
+        let checkbox = [("[ ] ", Checkbox::Unchecked), ("[-] ", Checkbox::Partial), ("[X] ", Checkbox::Checked), ("[x] ", Checkbox::Checked)]
+            .into_iter()
+            .find_map(|(prefix, checkbox)| {
+                let stripped = rest.strip_prefix(prefix).or_else(|| (rest == prefix.trim_end()).then_some(""))?;
+                rest = stripped;
+                Some(checkbox)
+            });
+        let mut term = None;
+        if !ordered {
+            if let Some((t, description)) = rest.split_once(" :: ") {
+                term = Some(parse_inline(t.trim()));
+                rest = description;
+            }
+        }
+
+        // The item goes on while lines are blank or indented past the bullet.
+        let mut body = vec![rest.to_string()];
+        let mut end = i;
+        while end < lines.len() {
+            let line = lines[end];
+            if line.trim().is_empty() {
+                if lines.get(end + 1).is_none_or(|next| next.trim().is_empty() || indent_of(next) <= indent) {
+                    break;
+                }
+            } else if indent_of(line) <= indent {
+                break;
+            }
+            end += 1;
+        }
+        let continuation = &lines[i..end];
+        let dedent = continuation
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| indent_of(line))
+            .min()
+            .unwrap_or(0);
+        body.extend(continuation.iter().map(|line| line.get(dedent..).unwrap_or("").to_string()));
+        i = end;
+
+        let body: Vec<&str> = body.iter().map(String::as_str).collect();
+        items.push(ListItem {
+            checkbox,
+            term,
+            content: parse_blocks(&body, keywords, document),
+        });
+
+        // A single blank line between items keeps the list going.
+        if i + 1 < lines.len() && lines[i].trim().is_empty() && list_item_indent(lines[i + 1]) == Some(indent) {
+            i += 1;
+        }
+    }
+
+    (Block::List { ordered, items }, i)
+}
+
+/// The text of `inlines` without their markup.
+pub fn plain_text(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|inline| match inline {
+            Inline::Text(text) | Inline::Code(text) => text.clone(),
+            Inline::Bold(inner) | Inline::Italic(inner) | Inline::Underline(inner) | Inline::Strike(inner) => {
+                plain_text(inner)
+            }
+            Inline::Link {
+                description: Some(description),
+                ..
+            } => plain_text(description),
+            Inline::Link { target, .. } => target.clone(),
+            Inline::LineBreak => " ".to_string(),
+        })
+        .collect()
+}
+
+/// The plain title that a `*Title` link target names. The target is org
+/// text, markup and all, so it is compared with `plain_text` of the
+/// headline titles only once its own markup is gone.
+pub fn link_title(target: &str) -> Option<String> {
+    let title = target.strip_prefix('*')?;
+    Some(plain_text(&parse_inline(title.trim())))
+}
+
+const MARKERS: [char; 6] = ['*', '/', '_', '+', '=', '~'];
+
+/// Parses org text markup: emphasis, verbatim, code, links and line breaks.
+pub fn parse_inline(text: &str) -> Vec<Inline> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut inlines = Vec::new();
+    let mut plain = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if let Some((inline, next)) = parse_link(&chars, i).or_else(|| parse_emphasis(&chars, i)) {
+            if !plain.is_empty() {
+                inlines.push(Inline::Text(std::mem::take(&mut plain)));
+            }
+            inlines.push(inline);
+            i = next;
+        } else if chars[i] == '\\' && chars.get(i + 1) == Some(&'\\') && matches!(chars.get(i + 2), None | Some('\n')) {
+            if !plain.is_empty() {
+                inlines.push(Inline::Text(std::mem::take(&mut plain)));
+            }
+            inlines.push(Inline::LineBreak);
+            i += 3;
+        } else {
+            plain.push(chars[i]);
+            i += 1;
+        }
+    }
+    if !plain.is_empty() {
+        inlines.push(Inline::Text(plain));
+    }
+    inlines
+}
+
+fn parse_link(chars: &[char], start: usize) -> Option<(Inline, usize)> {
+    if chars.get(start..start + 2) != Some(&['[', '['][..]) {
+        return None;
+    }
+    let end = (start + 2..chars.len().saturating_sub(1)).find(|&j| chars[j] == ']' && chars[j + 1] == ']')?;
+    let inner: String = chars[start + 2..end].iter().collect();
+    let (target, description) = match inner.split_once("][") {
+        Some((target, description)) => (target.to_string(), Some(parse_inline(description))),
+        None => (inner, None),
+    };
+    Some((Inline::Link { target, description }, end + 2))
+}
+
+fn parse_emphasis(chars: &[char], start: usize) -> Option<(Inline, usize)> {
+    let marker = chars[start];
+    if !MARKERS.contains(&marker) {
+        return None;
+    }
+    let pre_ok = start == 0 || chars[start - 1].is_whitespace() || "-({'\"".contains(chars[start - 1]);
+    let first = *chars.get(start + 1)?;
+    if !pre_ok || first.is_whitespace() || first == marker {
+        return None;
+    }
+    let end = (start + 2..chars.len()).find(|&j| {
+        chars[j] == marker
+            && !chars[j - 1].is_whitespace()
+            && chars.get(j + 1).is_none_or(|&c| c.is_whitespace() || "-.,;:!?')}\"[\\".contains(c))
+    })?;
+    let inner: String = chars[start + 1..end].iter().collect();
+    let inline = match marker {
+        '*' => Inline::Bold(parse_inline(&inner)),
+        '/' => Inline::Italic(parse_inline(&inner)),
+        '_' => Inline::Underline(parse_inline(&inner)),
+        '+' => Inline::Strike(parse_inline(&inner)),
+        _ => Inline::Code(inner),
+    };
+    Some((inline, end + 1))
+}
@@ -0,0 +1,304 @@
+use crate::replace_nulls_in_bytes;
+use std::str::FromStr;
+
+/// How groups are delimited on CMD's stdin, and so how separators in its
+/// output are read back.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Framing {
+    /// Groups separated by NUL. Input that already contains NUL is rejected,
+    /// since the child could not tell it from a group boundary.
+    #[default]
+    Nul,
+    /// Each group as a netstring, `LENGTH:BYTES,`.
+    Netstring,
+    /// Groups separated by NUL, with backslashes and NULs inside a group
+    /// written as `\\` and `\0`.
+    Escape,
+    /// Groups separated by a custom string, such as `\n\x1e\n`, which must
+    /// not occur in the input.
+    Separator(String),
+}
+
+impl FromStr for Framing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nul" => Ok(Framing::Nul),
+            "netstring" => Ok(Framing::Netstring),
+            "escape" => Ok(Framing::Escape),
+            _ => Err(format!(
+                "Unknown framing '{s}' (expected nul, netstring or escape)"
+            )),
+        }
+    }
+}
+
+/// Encodes `groups` for CMD's stdin.
+pub fn frame_groups(groups: &[String], framing: &Framing) -> Result<Vec<u8>, String> {
+    let mut output = Vec::with_capacity(groups.iter().map(|g| g.len() + 1).sum());
+    match framing {
+        Framing::Nul => join_separated(groups, "\0", &mut output)?,
+        Framing::Separator(separator) => join_separated(groups, separator, &mut output)?,
+        Framing::Netstring => {
+            for group in groups {
+                output.extend_from_slice(format!("{}:", group.len()).as_bytes());
+                output.extend_from_slice(group.as_bytes());
+                output.push(b',');
+            }
+        }
+        Framing::Escape => {
+            for (index, group) in groups.iter().enumerate() {
+                if index > 0 {
+                    output.push(0);
+                }
+                for &byte in group.as_bytes() {
+                    match byte {
+                        b'\\' => output.extend_from_slice(b"\\\\"),
+                        0 => output.extend_from_slice(b"\\0"),
+                        _ => output.push(byte),
+                    }
+                }
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// Where each of `groups` starts in the output of [`frame_groups`].
+pub fn group_offsets(groups: &[String], framing: &Framing) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(groups.len());
+    let mut offset = 0;
+    for group in groups {
+        offsets.push(offset);
+        offset += match framing {
+            Framing::Nul => group.len() + 1,
+            Framing::Separator(separator) => group.len() + separator.len(),
+            Framing::Netstring => group.len().to_string().len() + group.len() + 2,
+            Framing::Escape => group.len() + group.bytes().filter(|&b| b == b'\\' || b == 0).count() + 1,
+        };
+    }
+    offsets
+}
+
+fn join_separated(groups: &[String], separator: &str, output: &mut Vec<u8>) -> Result<(), String> {
+    if separator.is_empty() {
+        return Err("The group separator must not be empty".to_string());
+    }
+    let hint = if separator == "\0" {
+        "a NUL byte".to_string()
+    } else {
+        format!("the separator {separator:?}")
+    };
+    let mut line = 1;
+    for (index, group) in groups.iter().enumerate() {
+        if let Some(at) = group.find(separator) {
+            line += group[..at].matches('\n').count();
+            return Err(format!(
+                "Input contains {hint} at line {line}, which CMD would read as a group boundary; use netstring or escape framing or another separator"
+            ));
+        }
+        line += group.matches('\n').count();
+        if index > 0 {
+            output.extend_from_slice(separator.as_bytes());
+        }
+        output.extend_from_slice(group.as_bytes());
+    }
+    // A separator can still match across a boundary, e.g. `\n\n` after a
+    // group that ends with a newline.
+    let joined = String::from_utf8_lossy(output);
+    if !groups.is_empty() && !joined.split(separator).eq(groups.iter().map(String::as_str)) {
+        return Err(format!("The separator {separator:?} also matches across group boundaries"));
+    }
+    Ok(())
+}
+
+/// Replaces every occurrence of `separator` in `output` with `replacement`.
+pub fn replace_separators(output: &[u8], separator: &[u8], replacement: &str) -> Vec<u8> {
+    if separator.is_empty() {
+        return output.to_vec();
+    }
+    let mut result = Vec::with_capacity(output.len());
+    let mut rest = output;
+    while let Some(at) = rest.windows(separator.len()).position(|w| w == separator) {
+        result.extend_from_slice(&rest[..at]);
+        result.extend_from_slice(replacement.as_bytes());
+        rest = &rest[at + separator.len()..];
+    }
+    result.extend_from_slice(rest);
+    result
+}
+
+/// Translates CMD's stdout back: the separators of `framing` become
+/// `replacement`, and only those. With [`Framing::Escape`], `\0` and `\\`
+/// are then decoded to NUL and `\`; with [`Framing::Netstring`], the output
+/// must itself be a sequence of netstrings, each followed by `replacement`.
+pub fn unframe_output(output: &[u8], framing: &Framing, replacement: &str) -> Result<Vec<u8>, String> {
+    let mut translator = OutputTranslator::new(framing.clone(), replacement);
+    let mut result = translator.feed(output)?;
+    result.extend(translator.finish()?);
+    Ok(result)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NetstringState {
+    Length(usize),
+    Payload(usize),
+    Comma,
+}
+
+/// [`unframe_output`] for output that arrives in chunks. A separator or
+/// escape split across two chunks is held back until the next one.
+#[derive(Debug)]
+pub struct OutputTranslator {
+    framing: Framing,
+    replacement: String,
+    pending: Vec<u8>,
+    netstring: NetstringState,
+    offset: usize,
+}
+
+impl OutputTranslator {
+    pub fn new(framing: Framing, replacement: &str) -> Self {
+        OutputTranslator {
+            framing,
+            replacement: replacement.to_string(),
+            pending: Vec::new(),
+            netstring: NetstringState::Length(0),
+            offset: 0,
+        }
+    }
+
+    /// Translates the next chunk, returning what can be written out so far.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<u8>, String> {
+        let mut output = Vec::with_capacity(chunk.len());
+        match &self.framing {
+            Framing::Nul => output = replace_nulls_in_bytes(chunk, &self.replacement),
+            Framing::Separator(separator) if separator.is_empty() => output.extend_from_slice(chunk),
+            Framing::Separator(separator) => {
+                self.pending.extend_from_slice(chunk);
+                let separator = separator.as_bytes();
+                let mut rest = &self.pending[..];
+                while let Some(at) = rest.windows(separator.len()).position(|w| w == separator) {
+                    output.extend_from_slice(&rest[..at]);
+                    output.extend_from_slice(self.replacement.as_bytes());
+                    rest = &rest[at + separator.len()..];
+                }
+                // Whatever could still be the start of a separator waits.
+                let keep = rest.len().min(separator.len().saturating_sub(1));
+                output.extend_from_slice(&rest[..rest.len() - keep]);
+                self.pending = rest[rest.len() - keep..].to_vec();
+            }
+            Framing::Escape => {
+                let mut bytes = self.pending.iter().chain(chunk).copied().peekable();
+                let mut held = Vec::new();
+                while let Some(byte) = bytes.next() {
+                    match byte {
+                        0 => output.extend_from_slice(self.replacement.as_bytes()),
+                        b'\\' => match bytes.peek() {
+                            Some(b'0') => {
+                                output.push(0);
+                                bytes.next();
+                            }
+                            Some(b'\\') => {
+                                output.push(b'\\');
+                                bytes.next();
+                            }
+                            Some(_) => output.push(b'\\'),
+                            None => held.push(b'\\'),
+                        },
+                        _ => output.push(byte),
+                    }
+                }
+                self.pending = held;
+            }
+            Framing::Netstring => {
+                for &byte in chunk {
+                    self.netstring = match self.netstring {
+                        NetstringState::Length(length) => match byte {
+                            b'0'..=b'9' => {
+                                self.pending.push(byte);
+                                let length = length
+                                    .checked_mul(10)
+                                    .and_then(|l| l.checked_add(usize::from(byte - b'0')))
+                                    .ok_or_else(|| self.invalid())?;
+                                NetstringState::Length(length)
+                            }
+                            b':' if !self.pending.is_empty() => {
+                                self.pending.clear();
+                                if length == 0 {
+                                    NetstringState::Comma
+                                } else {
+                                    NetstringState::Payload(length)
+                                }
+                            }
+                            _ => return Err(self.invalid()),
+                        },
+                        NetstringState::Payload(remaining) => {
+                            output.push(byte);
+                            if remaining == 1 {
+                                NetstringState::Comma
+                            } else {
+                                NetstringState::Payload(remaining - 1)
+                            }
+                        }
+                        NetstringState::Comma if byte == b',' => {
+                            output.extend_from_slice(self.replacement.as_bytes());
+                            NetstringState::Length(0)
+                        }
+                        NetstringState::Comma => return Err(self.invalid()),
+                    };
+                    self.offset += 1;
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    /// Flushes what was held back; fails if the output ended inside a netstring.
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        if self.framing == Framing::Netstring
+            && (self.netstring != NetstringState::Length(0) || !self.pending.is_empty())
+        {
+            return Err(self.invalid());
+        }
+        Ok(self.pending)
+    }
+
+    fn invalid(&self) -> String {
+        format!("CMD output is not a valid netstring at byte {}", self.offset)
+    }
+}
+
+/// Prefixes every line of output that arrives in chunks with `prefix`.
+#[derive(Debug)]
+pub struct LinePrefixer {
+    prefix: Vec<u8>,
+    at_line_start: bool,
+}
+
+impl LinePrefixer {
+    pub fn new(prefix: &str) -> Self {
+        LinePrefixer {
+            prefix: prefix.as_bytes().to_vec(),
+            at_line_start: true,
+        }
+    }
+
+    /// Changes the prefix of the lines that start from now on.
+    pub fn set_prefix(&mut self, prefix: &str) {
+        self.prefix = prefix.as_bytes().to_vec();
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(chunk.len() + self.prefix.len());
+        for line in chunk.split_inclusive(|&b| b == b'\n') {
+            if self.at_line_start {
+                output.extend_from_slice(&self.prefix);
+            }
+            output.extend_from_slice(line);
+            self.at_line_start = line.ends_with(b"\n");
+        }
+        output
+    }
+}
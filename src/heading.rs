@@ -0,0 +1,184 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// A parsed org headline, e.g. `** TODO [#A] Write report :work:urgent:`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    pub level: usize,
+    pub keyword: Option<String>,
+    pub priority: Option<char>,
+    pub title: String,
+    pub tags: Vec<String>,
+}
+
+/// TODO keyword sequence of a document, split into active and done states.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TodoKeywords {
+    pub active: Vec<String>,
+    pub done: Vec<String>,
+}
+
+impl Default for TodoKeywords {
+    fn default() -> Self {
+        TodoKeywords {
+            active: vec!["TODO".to_string()],
+            done: vec!["DONE".to_string()],
+        }
+    }
+}
+
+impl TodoKeywords {
+    /// Collects keywords from `#+TODO:`, `#+SEQ_TODO:` and `#+TYP_TODO:` lines,
+    /// falling back to `TODO | DONE` when the document defines none.
+    pub fn from_document(input: &str) -> Self {
+        let mut keywords = TodoKeywords {
+            active: Vec::new(),
+            done: Vec::new(),
+        };
+
+        for line in input.lines() {
+            let Some(caps) = todo_line_re().captures(line) else {
+                continue;
+            };
+            let words: Vec<&str> = caps["words"].split_whitespace().collect();
+            let (active, done) = match words.iter().position(|w| *w == "|") {
+                Some(bar) => (&words[..bar], &words[bar + 1..]),
+                None if words.is_empty() => continue,
+                None => (&words[..words.len() - 1], &words[words.len() - 1..]),
+            };
+            keywords
+                .active
+                .extend(active.iter().map(|w| strip_fast_access(w)));
+            keywords
+                .done
+                .extend(done.iter().map(|w| strip_fast_access(w)));
+        }
+
+        if keywords.active.is_empty() && keywords.done.is_empty() {
+            TodoKeywords::default()
+        } else {
+            keywords
+        }
+    }
+
+    /// Whether `input` has a `#+TODO:`-style line of its own.
+    pub fn declared_in(input: &str) -> bool {
+        input.lines().any(|line| todo_line_re().is_match(line))
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.is_active(word) || self.is_done(word)
+    }
+
+    pub fn is_active(&self, word: &str) -> bool {
+        self.active.iter().any(|k| k == word)
+    }
+
+    pub fn is_done(&self, word: &str) -> bool {
+        self.done.iter().any(|k| k == word)
+    }
+}
+
+// `TODO(t)` and `DONE(d!)` carry fast-access keys that are not part of the keyword.
+fn strip_fast_access(word: &str) -> String {
+    match word.find('(') {
+        Some(paren) => word[..paren].to_string(),
+        None => word.to_string(),
+    }
+}
+
+fn todo_line_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)^#\+(?:SEQ_|TYP_)?TODO:(?P<words>.*)$").unwrap())
+}
+
+fn headline_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?P<stars>\*+)\s").unwrap())
+}
+
+fn tags_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?:^|[ \t]+)(?P<tags>:(?:[\w@#%]+:)+)$").unwrap())
+}
+
+/// Returns the star level of `line` if it is an org headline.
+pub fn heading_level(line: &str) -> Option<usize> {
+    headline_re()
+        .captures(line)
+        .map(|caps| caps.name("stars").unwrap().as_str().len())
+}
+
+pub fn parse_heading(line: &str, keywords: &TodoKeywords) -> Option<Heading> {
+    let level = heading_level(line)?;
+    let mut rest = line[level..].trim();
+
+    let mut keyword = None;
+    if let Some(word) = rest.split_whitespace().next() {
+        if keywords.contains(word) {
+            keyword = Some(word.to_string());
+            rest = rest[word.len()..].trim_start();
+        }
+    }
+
+    let mut priority = None;
+    let bytes = rest.as_bytes();
+    if bytes.len() >= 4
+        && bytes[0] == b'['
+        && bytes[1] == b'#'
+        && bytes[2].is_ascii_alphanumeric()
+        && bytes[3] == b']'
+    {
+        priority = Some(bytes[2] as char);
+        rest = rest[4..].trim_start();
+    }
+
+    let mut tags = Vec::new();
+    if let Some(caps) = tags_re().captures(rest) {
+        tags = caps["tags"]
+            .split(':')
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect();
+        rest = rest[..caps.get(0).unwrap().start()].trim_end();
+    }
+
+    Some(Heading {
+        level,
+        keyword,
+        priority,
+        title: rest.to_string(),
+        tags,
+    })
+}
+
+/// Tracks `#+BEGIN_...`/`#+END_...` blocks while walking a document line by line.
+#[derive(Debug, Default)]
+pub(crate) struct BlockTracker {
+    in_block: bool,
+}
+
+impl BlockTracker {
+    /// Feeds the next line and reports whether it belongs to a block
+    /// (including its delimiter lines), where org markup has no meaning.
+    pub(crate) fn in_block(&mut self, line: &str) -> bool {
+        let trimmed = line.trim_start();
+        if self.in_block {
+            if starts_with_ignore_case(trimmed, "#+end_") {
+                self.in_block = false;
+            }
+            true
+        } else if starts_with_ignore_case(trimmed, "#+begin_") {
+            self.in_block = true;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+pub(crate) fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.as_bytes()
+        .get(..prefix.len())
+        .is_some_and(|head| head.eq_ignore_ascii_case(prefix.as_bytes()))
+}
@@ -0,0 +1,341 @@
+use crate::export::{link_title, plain_text, Block, Checkbox, Document, ExportOptions, Inline, ListItem, PropertyMode};
+use crate::markdown::{heading_anchor, unique_anchor};
+
+/// Styles for the outline, the badges and the tables, so that the page
+/// needs no other files.
+const STYLE: &str = "body { max-width: 50rem; margin: 2rem auto; padding: 0 1rem; font-family: sans-serif; line-height: 1.5; }
+summary { cursor: pointer; }
+summary > h2, summary > h3, summary > h4, summary > h5, summary > h6 { display: inline; }
+section { margin-left: 1rem; }
+nav ul { list-style: none; padding-left: 1rem; }
+.badge { display: inline-block; padding: 0 0.4em; border-radius: 0.3em; font-size: 0.75em; font-weight: bold; vertical-align: middle; }
+.todo { background: #fde2e1; color: #a11; }
+.done { background: #def5e0; color: #164; }
+.priority { background: #fff1c2; color: #750; }
+.tag { background: #e4e9f7; color: #235; font-weight: normal; }
+.tags { float: right; }
+li.on, li.off, li.trans { list-style: none; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; }
+pre { background: #f6f8fa; padding: 0.8em; overflow-x: auto; }
+blockquote { border-left: 0.25em solid #ddd; margin-left: 0; padding-left: 1em; color: #555; }
+";
+
+/// Renders `document` as a standalone HTML page. Each headline opens a
+/// `<section>` that can be collapsed, with the `CUSTOM_ID` of the headline,
+/// or else an anchor made from its title, as the `id`.
+pub fn to_html(document: &Document, options: &ExportOptions) -> String {
+    let anchors = Anchors::new(&document.blocks);
+    let title = document
+        .keyword("TITLE")
+        .map(str::to_string)
+        .or_else(|| options.title.clone())
+        .or_else(|| anchors.titles.first().cloned())
+        .unwrap_or_default();
+
+    let mut output = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    output.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    output.push_str(&format!("<title>{}</title>\n", escape_html(&title)));
+    if options.properties == PropertyMode::FrontMatter {
+        for (key, value) in document.front_matter().into_iter().filter(|(key, _)| key != "title") {
+            output.push_str(&format!(
+                "<meta name=\"{}\" content=\"{}\">\n",
+                escape_html(&key),
+                escape_html(value)
+            ));
+        }
+    }
+    output.push_str(&format!("<style>\n{STYLE}</style>\n</head>\n<body>\n"));
+    if !title.is_empty() {
+        output.push_str(&format!("<h1 class=\"title\">{}</h1>\n", escape_html(&title)));
+    }
+    output.push_str(&render_outline(&document.blocks, &anchors));
+
+    output.push_str("<main>\n");
+    // The levels of the open sections, innermost last.
+    let mut open: Vec<usize> = Vec::new();
+    let mut heading_index = 0;
+    for block in &document.blocks {
+        if let Block::Heading { level, .. } = block {
+            while open.last().is_some_and(|open| open >= level) {
+                open.pop();
+                output.push_str("</details>\n</section>\n");
+            }
+            open.push(*level);
+            output.push_str(&format!(
+                "<section id=\"{}\" class=\"level-{level}\">\n<details open>\n<summary>{}</summary>\n",
+                escape_html(&anchors.ids[heading_index]),
+                render_heading(block, &anchors)
+            ));
+            heading_index += 1;
+        } else {
+            output.push_str(&render_block(block, &anchors));
+        }
+    }
+    for _ in open {
+        output.push_str("</details>\n</section>\n");
+    }
+    output.push_str("</main>\n</body>\n</html>\n");
+    output
+}
+
+/// The `id` and plain title of every headline, in document order.
+struct Anchors {
+    ids: Vec<String>,
+    titles: Vec<String>,
+}
+
+impl Anchors {
+    fn new(blocks: &[Block]) -> Self {
+        let mut anchors = Anchors {
+            ids: Vec::new(),
+            titles: Vec::new(),
+        };
+        for block in blocks {
+            let Block::Heading { title, properties, .. } = block else {
+                continue;
+            };
+            let title = plain_text(title);
+            let custom_id = properties
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case("CUSTOM_ID"))
+                .map(|(_, value)| value.clone());
+            let id = custom_id.unwrap_or_else(|| match heading_anchor(&title) {
+                anchor if anchor.is_empty() => "section".to_string(),
+                anchor => anchor,
+            });
+            anchors.ids.push(unique_anchor(&anchors.ids, id));
+            anchors.titles.push(title);
+        }
+        anchors
+    }
+
+    /// The index of the headline that an org link target names: a
+    /// `#custom-id` or a `*title`.
+    fn find(&self, target: &str) -> Option<usize> {
+        if let Some(id) = target.strip_prefix('#') {
+            return self.ids.iter().position(|known| known == id);
+        }
+        let title = link_title(target)?;
+        self.titles.iter().position(|known| *known == title)
+    }
+}
+
+/// The table of contents: nested lists of links, collapsible as a whole.
+fn render_outline(blocks: &[Block], anchors: &Anchors) -> String {
+    let levels: Vec<usize> = blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Heading { level, .. } => Some(*level),
+            _ => None,
+        })
+        .collect();
+    if levels.is_empty() {
+        return String::new();
+    }
+
+    let mut output = String::from("<nav id=\"table-of-contents\">\n<details open>\n<summary>Contents</summary>\n");
+    let mut open: Vec<usize> = Vec::new();
+    for (index, &level) in levels.iter().enumerate() {
+        while open.last().is_some_and(|&open| open > level) {
+            open.pop();
+            output.push_str("</li>\n</ul>\n");
+        }
+        if open.last() == Some(&level) {
+            output.push_str("</li>\n");
+        } else {
+            if !open.is_empty() {
+                output.push('\n');
+            }
+            open.push(level);
+            output.push_str("<ul>\n");
+        }
+        output.push_str(&format!(
+            "<li><a href=\"#{}\">{}</a>",
+            escape_html(&anchors.ids[index]),
+            escape_html(&anchors.titles[index])
+        ));
+    }
+    for _ in open {
+        output.push_str("</li>\n</ul>\n");
+    }
+    output.push_str("</details>\n</nav>\n");
+    output
+}
+
+fn render_heading(block: &Block, anchors: &Anchors) -> String {
+    let Block::Heading {
+        level,
+        keyword,
+        done,
+        priority,
+        title,
+        tags,
+        ..
+    } = block
+    else {
+        return String::new();
+    };
+    // The page title is the `<h1>`.
+    let tag = format!("h{}", (level + 1).min(6));
+    let mut text = format!("<{tag}>");
+    if let Some(keyword) = keyword {
+        let class = if *done { "done" } else { "todo" };
+        text.push_str(&format!("<span class=\"badge {class}\">{}</span> ", escape_html(keyword)));
+    }
+    if let Some(priority) = priority {
+        text.push_str(&format!("<span class=\"badge priority\">#{priority}</span> "));
+    }
+    text.push_str(&render_inlines(title, anchors));
+    if !tags.is_empty() {
+        text.push_str(" <span class=\"tags\">");
+        for tag in tags {
+            text.push_str(&format!("<span class=\"badge tag\">{}</span>", escape_html(tag)));
+        }
+        text.push_str("</span>");
+    }
+    text.push_str(&format!("</{tag}>"));
+    text
+}
+
+fn render_blocks(blocks: &[Block], anchors: &Anchors) -> String {
+    blocks.iter().map(|block| render_block(block, anchors)).collect()
+}
+
+fn render_block(block: &Block, anchors: &Anchors) -> String {
+    match block {
+        Block::Heading { .. } => format!("{}\n", render_heading(block, anchors)),
+        Block::Paragraph(inlines) => format!("<p>{}</p>\n", render_inlines(inlines, anchors)),
+        Block::List { ordered, items } => render_list(*ordered, items, anchors),
+        Block::Table { header, rows } => render_table(*header, rows, anchors),
+        Block::Code { language, code } => {
+            let class = language
+                .as_ref()
+                .map(|language| format!(" class=\"language-{}\"", escape_html(language)))
+                .unwrap_or_default();
+            format!("<pre><code{class}>{}</code></pre>\n", escape_html(code))
+        }
+        Block::Quote(blocks) => format!("<blockquote>\n{}</blockquote>\n", render_blocks(blocks, anchors)),
+        Block::Raw { format, text } if format == "html" => text.clone(),
+        Block::Raw { .. } => String::new(),
+        Block::Rule => "<hr>\n".to_string(),
+    }
+}
+
+fn render_list(ordered: bool, items: &[ListItem], anchors: &Anchors) -> String {
+    // A list with `term :: description` items is a description list.
+    if items.iter().any(|item| item.term.is_some()) {
+        let mut output = String::from("<dl>\n");
+        for item in items {
+            let term = item.term.as_deref().unwrap_or_default();
+            output.push_str(&format!("<dt>{}</dt>\n", render_inlines(term, anchors)));
+            output.push_str(&format!("<dd>{}</dd>\n", render_item_content(&item.content, anchors)));
+        }
+        output.push_str("</dl>\n");
+        return output;
+    }
+
+    let tag = if ordered { "ol" } else { "ul" };
+    let mut output = format!("<{tag}>\n");
+    for item in items {
+        // The classes of `ox-html`.
+        let (class, checkbox) = match item.checkbox {
+            Some(Checkbox::Checked) => (" class=\"on\"", "<input type=\"checkbox\" checked disabled> "),
+            Some(Checkbox::Partial) => (" class=\"trans\"", "<input type=\"checkbox\" disabled> "),
+            Some(Checkbox::Unchecked) => (" class=\"off\"", "<input type=\"checkbox\" disabled> "),
+            None => ("", ""),
+        };
+        output.push_str(&format!(
+            "<li{class}>{checkbox}{}</li>\n",
+            render_item_content(&item.content, anchors)
+        ));
+    }
+    output.push_str(&format!("</{tag}>\n"));
+    output
+}
+
+/// The blocks of a list item, with a leading paragraph left unwrapped.
+fn render_item_content(blocks: &[Block], anchors: &Anchors) -> String {
+    match blocks.split_first() {
+        Some((Block::Paragraph(inlines), [])) => render_inlines(inlines, anchors),
+        Some((Block::Paragraph(inlines), rest)) => {
+            format!("{}\n{}", render_inlines(inlines, anchors), render_blocks(rest, anchors))
+        }
+        _ => format!("\n{}", render_blocks(blocks, anchors)),
+    }
+}
+
+fn render_table(header: bool, rows: &[Vec<Vec<Inline>>], anchors: &Anchors) -> String {
+    let render_row = |row: &[Vec<Inline>], cell_tag: &str| {
+        let cells: String = row
+            .iter()
+            .map(|cell| format!("<{cell_tag}>{}</{cell_tag}>", render_inlines(cell, anchors)))
+            .collect();
+        format!("<tr>{cells}</tr>\n")
+    };
+
+    let mut output = String::from("<table>\n");
+    let mut body = rows;
+    if let Some((first, rest)) = rows.split_first().filter(|_| header) {
+        output.push_str(&format!("<thead>\n{}</thead>\n", render_row(first, "th")));
+        body = rest;
+    }
+    if !body.is_empty() {
+        output.push_str("<tbody>\n");
+        for row in body {
+            output.push_str(&render_row(row, "td"));
+        }
+        output.push_str("</tbody>\n");
+    }
+    output.push_str("</table>\n");
+    output
+}
+
+fn render_inlines(inlines: &[Inline], anchors: &Anchors) -> String {
+    inlines.iter().map(|inline| render_inline(inline, anchors)).collect()
+}
+
+fn render_inline(inline: &Inline, anchors: &Anchors) -> String {
+    match inline {
+        Inline::Text(text) => escape_html(text),
+        Inline::Bold(inner) => format!("<b>{}</b>", render_inlines(inner, anchors)),
+        Inline::Italic(inner) => format!("<i>{}</i>", render_inlines(inner, anchors)),
+        Inline::Underline(inner) => format!("<u>{}</u>", render_inlines(inner, anchors)),
+        Inline::Strike(inner) => format!("<del>{}</del>", render_inlines(inner, anchors)),
+        Inline::Code(code) => format!("<code>{}</code>", escape_html(code)),
+        Inline::Link { target, description } => {
+            let heading = anchors.find(target);
+            let title = link_title(target);
+            let href = match (heading, &title) {
+                (Some(index), _) => format!("#{}", anchors.ids[index]),
+                // A headline that is not in this document.
+                (None, Some(title)) => format!("#{}", heading_anchor(title)),
+                (None, None) => target.strip_prefix("file:").unwrap_or(target).to_string(),
+            };
+            // Links to headlines show the title, as in org.
+            let text = match (description, heading, title) {
+                (Some(description), _, _) => render_inlines(description, anchors),
+                (None, Some(index), _) => escape_html(&anchors.titles[index]),
+                (None, None, Some(title)) => escape_html(&title),
+                (None, None, None) => escape_html(target),
+            };
+            format!("<a href=\"{}\">{text}</a>", escape_html(&href))
+        }
+        Inline::LineBreak => "<br>\n".to_string(),
+    }
+}
+
+/// Escapes text for HTML content and double-quoted attributes.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
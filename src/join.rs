@@ -0,0 +1,83 @@
+use crate::levels::shift_levels;
+use crate::section::push_group;
+use crate::split::preamble;
+use regex::Regex;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Default)]
+pub struct JoinOptions {
+    /// Nest all file headlines under one headline with this title.
+    pub under_heading: Option<String>,
+    /// Extra levels added to every headline of the joined document.
+    pub demote: usize,
+    /// Combine file headlines with identical titles into one.
+    pub merge_duplicates: bool,
+}
+
+fn title_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?im)^#\+TITLE:[ \t]*(?P<title>.*?)[ \t]*\r?$").unwrap())
+}
+
+fn title_line_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?im)^#\+TITLE:[^\n]*\n?").unwrap())
+}
+
+/// Returns the `#+TITLE:` of an org document, if it sets a non-empty one.
+pub fn document_title(input: &str) -> Option<String> {
+    title_re()
+        .captures(preamble(input))
+        .map(|caps| caps["title"].to_string())
+        .filter(|title| !title.is_empty())
+}
+
+/// Combines documents into one, nesting each under a generated headline
+/// named after its `#+TITLE` or, failing that, the `name` it was given.
+///
+/// The preamble of each document, minus its `#+TITLE` line, becomes the
+/// body of that headline, and its sections are demoted to sit below it.
+pub fn join_documents(
+    documents: &[(String, String)],
+    options: &JoinOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let base = options.demote + usize::from(options.under_heading.is_some());
+    let file_level = base + 1;
+
+    let mut entries: Vec<(String, String)> = Vec::new();
+    for (name, content) in documents {
+        let title = document_title(content).unwrap_or_else(|| name.clone());
+        let head = preamble(content);
+        let body = title_line_re().replace_all(head, "");
+        let sections = shift_levels(&content[head.len()..], file_level as isize)?;
+
+        let mut text = String::new();
+        push_group(&mut text, &body);
+        push_group(&mut text, &sections);
+
+        match entries
+            .iter_mut()
+            .find(|(t, _)| options.merge_duplicates && *t == title)
+        {
+            Some((_, merged)) => push_group(merged, &text),
+            None => entries.push((title, text)),
+        }
+    }
+
+    let mut output = String::new();
+    if let Some(heading) = &options.under_heading {
+        output.push_str(&format!("{} {heading}\n", "*".repeat(base)));
+    }
+    for (title, text) in entries {
+        push_group(
+            &mut output,
+            &format!("{} {title}\n", "*".repeat(file_level)),
+        );
+        push_group(&mut output, &text);
+    }
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+
+    Ok(output)
+}
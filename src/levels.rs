@@ -0,0 +1,93 @@
+use crate::heading::{heading_level, BlockTracker};
+use crate::outline::heading_spans;
+use regex::Regex;
+
+/// Shifts every headline in `text` by `delta` stars, leaving lines inside
+/// `#+BEGIN_...`/`#+END_...` blocks untouched.
+///
+/// Fails without changing anything if a headline would drop below level 1.
+pub fn shift_levels(text: &str, delta: isize) -> Result<String, Box<dyn std::error::Error>> {
+    let mut output = String::with_capacity(text.len());
+    let mut blocks = BlockTracker::default();
+
+    for (number, line) in text.split_inclusive('\n').enumerate() {
+        let in_block = blocks.in_block(line);
+        match heading_level(line) {
+            Some(level) if !in_block => shift_line(&mut output, line, level, delta, number + 1)?,
+            _ => output.push_str(line),
+        }
+    }
+
+    Ok(output)
+}
+
+fn shift_line(
+    output: &mut String,
+    line: &str,
+    level: usize,
+    delta: isize,
+    number: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let shifted = level as isize + delta;
+    if shifted < 1 {
+        return Err(format!(
+            "Cannot shift line {number} by {delta}: level {level} heading would drop below level 1: {}",
+            line.trim_end()
+        )
+        .into());
+    }
+    output.push_str(&"*".repeat(shifted as usize));
+    output.push_str(&line[level..]);
+    Ok(())
+}
+
+/// Renumbers the stars of `group` so that its first headline is at level 1.
+/// Text without headlines is returned unchanged.
+pub fn promote_to_top(group: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut blocks = BlockTracker::default();
+    let first = group.split_inclusive('\n').find_map(|line| {
+        let in_block = blocks.in_block(line);
+        heading_level(line).filter(|_| !in_block)
+    });
+    match first {
+        Some(level) => shift_levels(group, 1 - level as isize),
+        None => Ok(group.to_string()),
+    }
+}
+
+/// Shifts every subtree whose headline starts a group at `level` and
+/// matches `select` (every such subtree when `select` is `None`) by
+/// `delta`, children included. A subtree inside one already shifted is not
+/// shifted again. The preamble and lines inside blocks are never shifted.
+pub fn shift_sections(
+    input: &str,
+    level: usize,
+    delta: isize,
+    select: Option<&Regex>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut selected: Vec<(usize, usize)> = Vec::new();
+    for span in heading_spans(input) {
+        let nested = selected.last().is_some_and(|&(_, end)| span.start < end);
+        let headline = input[span.start..span.body_start].trim_end();
+        if !nested && span.level <= level && select.is_none_or(|re| re.is_match(headline)) {
+            selected.push((span.start, span.end));
+        }
+    }
+
+    let mut output = String::with_capacity(input.len());
+    let mut blocks = BlockTracker::default();
+    let mut offset = 0;
+    // The spans are sorted and disjoint: skip those the lines are past.
+    let mut spans = selected.iter().peekable();
+    for (number, line) in input.split_inclusive('\n').enumerate() {
+        let in_block = blocks.in_block(line);
+        while spans.next_if(|&&(_, end)| end <= offset).is_some() {}
+        let in_selected = spans.peek().is_some_and(|&&(start, _)| start <= offset);
+        match heading_level(line) {
+            Some(level) if in_selected && !in_block => shift_line(&mut output, line, level, delta, number + 1)?,
+            _ => output.push_str(line),
+        }
+        offset += line.len();
+    }
+    Ok(output)
+}
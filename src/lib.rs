@@ -1,44 +1,99 @@
-use regex::Regex;
+pub mod archive;
+pub mod checkbox;
+pub mod completion;
+pub mod config;
+pub mod dedupe;
+pub mod detector;
+pub mod diff;
+pub mod export;
+pub mod framing;
+pub mod heading;
+pub mod html;
+pub mod join;
+pub mod levels;
+pub mod limits;
+pub mod markdown;
+pub mod outline;
+pub mod refile;
+pub mod section;
+pub mod signals;
+pub mod sort;
+pub mod split;
+pub mod stats;
+pub mod template;
 
-pub fn group_org_sections(input: &str, level: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let headline_re = Regex::new(r"(?m)^(?P<stars>\*+)\s")?;
+use detector::{group_sections, OrgHeadings};
 
-    let mut groups: Vec<String> = Vec::new();
-    let mut current = String::new();
+/// Splits `input` into groups, starting a new group at every headline with at
+/// most `level` stars. Text before the first such headline is a group of its own.
+///
+/// The split is lossless: `groups.concat()` is always byte-for-byte equal to
+/// `input`. Lines are kept with their own endings, so `\r\n` stays `\r\n`, a
+/// last line without a newline stays without one, and NUL bytes are passed
+/// through untouched. Groups are never empty, so an empty input has no groups.
+pub fn group_org_sections(input: &str, level: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    Ok(group_sections(input, level, &mut OrgHeadings))
+}
 
-    for line in input.split_inclusive('\n') {
-        if let Some(caps) = headline_re.captures(line) {
-            let star_count = caps.name("stars").unwrap().as_str().len();
+/// Decodes backslash escapes in a single pass: `\n`, `\t`, `\r`, `\0`,
+/// `\\`, `\a`, `\e`, `\f`, `\v`, `\xHH` (ASCII, up to `\x7F`) and `\u{H...}`.
+/// Unknown or malformed escapes are errors rather than passed through.
+pub fn process_escape_sequences(input: &str) -> Result<String, String> {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.char_indices();
 
-            // Start a new group when we see a heading at or above the target level
-            if star_count <= level {
-                // Close current group if it has content
-                if !current.is_empty() {
-                    groups.push(current);
-                    current = String::new();
+    while let Some((_, c)) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        let Some((at, escape)) = chars.next() else {
+            return Err(format!("Trailing backslash in '{input}'"));
+        };
+        let decoded = match escape {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            'a' => '\x07',
+            'e' => '\x1b',
+            'f' => '\x0c',
+            'v' => '\x0b',
+            'x' => {
+                let hex = input
+                    .get(at + 1..at + 3)
+                    .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .ok_or_else(|| format!("Expected two hex digits after '\\x' in '{input}'"))?;
+                let value = u8::from_str_radix(hex, 16).unwrap();
+                if !value.is_ascii() {
+                    return Err(format!(
+                        "'\\x{hex}' is not ASCII in '{input}'; use '\\u{{{hex}}}' for the character U+00{hex}"
+                    ));
                 }
+                chars.nth(1);
+                char::from(value)
             }
-        }
-        
-        // Always include content in the current group
-        current.push_str(line);
-    }
-    
-    // Add the final group if it has content
-    if !current.is_empty() {
-        groups.push(current);
+            'u' => {
+                let rest = &input[at + 1..];
+                let hex = rest
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.split_once('}'))
+                    .map(|(hex, _)| hex)
+                    .filter(|hex| (1..=6).contains(&hex.len()) && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .ok_or_else(|| format!("Expected '\\u{{HEX}}' with 1 to 6 hex digits in '{input}'"))?;
+                let value = u32::from_str_radix(hex, 16).unwrap();
+                let decoded = char::from_u32(value)
+                    .ok_or_else(|| format!("'\\u{{{hex}}}' is not a valid character in '{input}'"))?;
+                chars.nth(hex.len() + 1);
+                decoded
+            }
+            other => return Err(format!("Unknown escape sequence '\\{other}' in '{input}'")),
+        };
+        output.push(decoded);
     }
 
-    Ok(groups)
-}
-
-pub fn process_escape_sequences(input: &str) -> String {
-    input
-        .replace("\\n", "\n")
-        .replace("\\t", "\t")
-        .replace("\\r", "\r")
-        .replace("\\0", "\0")
-        .replace("\\\\", "\\")
+    Ok(output)
 }
 
 pub fn replace_nulls_in_bytes(input: &[u8], replacement: &str) -> Vec<u8> {
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+/// Parses durations like `90`, `1.5s`, `500ms`, `2m` or `1h`. Bare numbers
+/// are seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration '{s}' (expected e.g. 30s, 500ms, 2m or 1h)");
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "" | "s" => number,
+        "ms" => number / 1000.0,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(invalid()),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+/// Parses sizes like `512M`, `2G`, `64k` or a plain number of bytes, in
+/// binary units.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid size '{s}' (expected e.g. 512M, 2G or 65536)");
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let shift = match unit.to_ascii_uppercase().trim_end_matches('B') {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(invalid()),
+    };
+    number.checked_mul(1 << shift).ok_or_else(invalid)
+}
+
+/// Resource limits applied to each child command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// `RLIMIT_CPU`: CPU time in seconds, after which the child gets `SIGXCPU`.
+    pub cpu_seconds: Option<u64>,
+    /// `RLIMIT_AS`: address space in bytes, beyond which allocations fail.
+    pub memory_bytes: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        self.cpu_seconds.is_none() && self.memory_bytes.is_none()
+    }
+
+    /// Applies the limits to the current process. Meant to run between
+    /// `fork` and `exec`, so it only calls `setrlimit`.
+    #[cfg(unix)]
+    pub fn apply(&self) -> std::io::Result<()> {
+        let set = |resource, value: u64| {
+            let limit = libc::rlimit {
+                rlim_cur: value as libc::rlim_t,
+                rlim_max: value as libc::rlim_t,
+            };
+            // SAFETY: `limit` is a valid rlimit that outlives the call.
+            if unsafe { libc::setrlimit(resource, &limit) } == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        };
+        if let Some(seconds) = self.cpu_seconds {
+            set(libc::RLIMIT_CPU, seconds)?;
+        }
+        if let Some(bytes) = self.memory_bytes {
+            set(libc::RLIMIT_AS, bytes)?;
+        }
+        Ok(())
+    }
+}
@@ -1,57 +1,1150 @@
-use clap::{Arg, Command as ClapCommand};
-use std::ffi::OsStr;
+use clap::builder::{
+    NonEmptyStringValueParser, PossibleValue, PossibleValuesParser, StringValueParser, TypedValueParser,
+};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand, ValueHint};
+use clap_complete::Shell;
+use std::ffi::{OsStr, OsString};
+use std::fs;
 use std::io::{self, Read, Write};
-use std::process::{Command, Stdio};
-use org_grouper::{group_org_sections, process_escape_sequences, replace_nulls_in_bytes};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use regex::{Regex, RegexBuilder};
+use org_grouper::archive::{archive_done, archive_file_header, ArchiveOptions};
+use org_grouper::checkbox::{has_incomplete_checkboxes, update_cookies};
+use org_grouper::completion::CompletionValues;
+use org_grouper::config::{config_paths, load_config};
+use org_grouper::dedupe::{dedupe_groups, duplicate_clusters, DedupeMode};
+use org_grouper::detector::{group_sections, starts_with_heading, HeadingDetector, InputFormat, RegexHeadings};
+use org_grouper::diff::unified_diff;
+use org_grouper::export::{export, ExportFormat, ExportOptions};
+use org_grouper::framing::{frame_groups, group_offsets, Framing, LinePrefixer, OutputTranslator};
+use org_grouper::heading::{heading_level, TodoKeywords};
+use org_grouper::join::{document_title, join_documents, JoinOptions};
+use org_grouper::levels::shift_sections;
+use org_grouper::outline::heading_spans;
+use org_grouper::limits::{parse_duration, parse_size, ResourceLimits};
+use org_grouper::refile::refile;
+use org_grouper::section::group_line_numbers;
+#[cfg(unix)]
+use org_grouper::signals::{ChildTarget, SignalForwarder, Watchdog};
+use org_grouper::signals::{exit_code, is_broken_pipe, EXIT_BROKEN_PIPE, EXIT_TIMED_OUT};
+use org_grouper::sort::{sort_sections, SortKey};
+use org_grouper::stats::document_stats;
+use org_grouper::split::{preamble, split_groups, SplitFile, SplitOptions, DEFAULT_SPLIT_TEMPLATE};
+use org_grouper::template::{render_known_placeholders, render_template, shell_quote, SectionFields};
+use org_grouper::{process_escape_sequences, replace_nulls_in_bytes};
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let matches = ClapCommand::new("org_grouper")
+const DEFAULT_LIST_TEMPLATE: &str = "{line}: {heading}";
+
+/// Options for reading the input and choosing the groups, shared by the
+/// main command and the subcommands that work on groups.
+fn input_args() -> Vec<Arg> {
+    vec![
+        Arg::new("input")
+            .long("input")
+            .num_args(1)
+            .value_name("FILE")
+            .value_hint(ValueHint::FilePath)
+            .help("Read the org document from FILE instead of stdin"),
+        Arg::new("group_headings_at")
+            .long("group-headings-at")
+            .num_args(1)
+            .value_name("LEVEL")
+            .value_parser(LevelValueParser)
+            .hide_possible_values(true)
+            .help("Org heading star level to group at (e.g., 1 for '*', 2 for '**')")
+            .default_value("1"),
+        Arg::new("incomplete_checkboxes")
+            .long("incomplete-checkboxes")
+            .action(ArgAction::SetTrue)
+            .help("Only keep groups that contain unchecked '- [ ]' checkboxes"),
+        Arg::new("update_cookies")
+            .long("update-cookies")
+            .action(ArgAction::SetTrue)
+            .help("Recompute [n/m] and [p%] statistics cookies before grouping; without CMD, print the result"),
+        Arg::new("sort_by")
+            .long("sort-by")
+            .num_args(1)
+            .value_name("KEY")
+            .help("Reorder sibling groups at the grouping level by heading, todo, priority, deadline, property:NAME or created; without CMD, print the result"),
+        Arg::new("reverse")
+            .long("reverse")
+            .action(ArgAction::SetTrue)
+            .requires("sort_by")
+            .help("Sort in descending order with --sort-by"),
+        Arg::new("dedupe")
+            .long("dedupe")
+            .num_args(1)
+            .value_name("exact|heading|normalized")
+            .help("Drop groups that repeat an earlier group; 'normalized' ignores whitespace, timestamps and checkbox state; without CMD, print the result"),
+        Arg::new("dedupe_report")
+            .long("dedupe-report")
+            .action(ArgAction::SetTrue)
+            .requires("dedupe")
+            .conflicts_with("sort_by")
+            .help("Instead of dropping duplicates, list each cluster of duplicate groups with their line numbers in the input"),
+        Arg::new("shift_levels")
+            .long("shift-levels")
+            .num_args(1)
+            .value_name("+N|-N")
+            .allow_hyphen_values(true)
+            .help("Promote (-N) or demote (+N) selected groups with their whole subtrees, leaving block contents alone; without CMD, print the result"),
+        Arg::new("select")
+            .long("select")
+            .num_args(1)
+            .value_name("REGEX")
+            .requires("shift_levels")
+            .help("Only shift groups whose headline matches REGEX"),
+    ]
+}
+
+/// The syntax of the input, for the commands that only need to know where
+/// groups start.
+fn input_format_args() -> Vec<Arg> {
+    vec![
+        Arg::new("input_format")
+            .long("input-format")
+            .num_args(1)
+            .value_name("FORMAT")
+            .value_parser(["org", "markdown", "rst", "asciidoc", "outline"])
+            .default_value("org")
+            .help("Syntax of the input headings: org stars, Markdown '#' or underlines, reStructuredText adornments, AsciiDoc '==' or Emacs outline-mode ';;;'"),
+        Arg::new("heading_regex")
+            .long("heading-regex")
+            .num_args(1)
+            .value_name("REGEX")
+            .help("Detect headings with REGEX instead, matched against each line; its 'level' capture holds the level, or the length of its 'stars' capture is the level"),
+    ]
+}
+
+/// Options for converting org groups to another format.
+fn export_args() -> Vec<Arg> {
+    vec![
+        Arg::new("format")
+            .long("format")
+            .num_args(1)
+            .value_name("FORMAT")
+            .value_parser(["org", "markdown", "html"])
+            .default_value("org")
+            .help("Convert the org input to FORMAT: the whole selection when printing it, each group when piping to CMD"),
+        Arg::new("properties")
+            .long("properties")
+            .num_args(1)
+            .value_name("MODE")
+            .value_parser(["drop", "front-matter"])
+            .default_value("drop")
+            .help("With --format, drop property drawers, or put the first one, with #+TITLE and the like, in YAML front matter"),
+    ]
+}
+
+/// Options for running CMD on the groups.
+fn exec_args() -> Vec<Arg> {
+    vec![
+        Arg::new("out_replace_nulls")
+            .long("out-replace-nulls")
+            .num_args(1)
+            .value_name("yes|no")
+            .help("If 'yes', replace NUL (\\0) in CMD output with replacement string")
+            .default_value("yes"),
+        Arg::new("out_replace_nulls_with")
+            .long("out-replace-nulls-with")
+            .num_args(1)
+            .value_name("STRING")
+            .help("String to replace NUL (\\0) characters with (supports escape sequences like \\n, \\t)")
+            .default_value("\\n"),
+        Arg::new("err_replace_nulls")
+            .long("err-replace-nulls")
+            .num_args(1)
+            .value_name("yes|no")
+            .help("If 'yes', replace NUL (\\0) in CMD stderr with the replacement string (default: same as --out-replace-nulls)"),
+        Arg::new("err_prefix")
+            .long("err-prefix")
+            .num_args(1)
+            .value_name("STRING")
+            .help("Prefix every line CMD writes to stderr with STRING, where placeholders like {line} and {title} name the group CMD is being fed (supports escape sequences)"),
+        Arg::new("framing")
+            .long("framing")
+            .num_args(1)
+            .value_name("nul|netstring|escape")
+            .help("How groups are delimited on CMD's stdin: NUL-separated (input must not contain NUL), netstrings (CMD's output is passed through unless --out-replace-nulls=yes), or NUL-separated with \\ and \\0 escapes")
+            .default_value("nul"),
+        Arg::new("in_separator")
+            .long("in-separator")
+            .num_args(1)
+            .value_name("STRING")
+            .value_parser(NonEmptyStringValueParser::new())
+            .conflicts_with("framing")
+            .help("Separate groups on CMD's stdin with STRING instead of NUL (supports escape sequences)"),
+        Arg::new("out_separator")
+            .long("out-separator")
+            .num_args(1)
+            .value_name("STRING")
+            .value_parser(NonEmptyStringValueParser::new())
+            .conflicts_with("framing")
+            .help("Separator to replace in CMD output (default: the input separator; supports escape sequences)"),
+        Arg::new("sh")
+            .long("sh")
+            .num_args(1)
+            .value_name("SCRIPT")
+            .conflicts_with("cmd")
+            .help("Run SCRIPT through the shell instead of CMD, e.g. --sh 'grep TODO | sort'"),
+        Arg::new("shell")
+            .long("shell")
+            .num_args(1)
+            .value_name("PATH")
+            .value_hint(ValueHint::ExecutablePath)
+            .default_value("/bin/sh")
+            .help("Shell that runs the --sh script with -c"),
+        Arg::new("each")
+            .long("each")
+            .action(ArgAction::SetTrue)
+            .conflicts_with_all(["framing", "in_separator"])
+            .help("Run CMD once per group with the group on stdin; {title}, {index}, {property:NAME}, ... in CMD, --sh and --err-prefix are replaced by the group's metadata ({{ and }} for literal braces)"),
+        Arg::new("timeout")
+            .long("timeout")
+            .num_args(1)
+            .value_name("DURATION")
+            .help("Stop CMD if the whole run takes longer than DURATION (e.g. 30s, 2m) and exit with status 124"),
+        Arg::new("section_timeout")
+            .long("section-timeout")
+            .num_args(1)
+            .value_name("DURATION")
+            .requires("each")
+            .help("With --each, stop CMD if a single group takes longer than DURATION"),
+        Arg::new("kill_after")
+            .long("kill-after")
+            .num_args(1)
+            .value_name("DURATION")
+            .default_value("5s")
+            .help("Send SIGKILL if CMD is still running this long after the SIGTERM of a timeout"),
+        Arg::new("limit_cpu")
+            .long("limit-cpu")
+            .num_args(1)
+            .value_name("SECONDS")
+            .help("Limit the CPU time of each CMD (RLIMIT_CPU)"),
+        Arg::new("limit_memory")
+            .long("limit-memory")
+            .num_args(1)
+            .value_name("SIZE")
+            .help("Limit the address space of each CMD, e.g. 512M (RLIMIT_AS)"),
+        Arg::new("diff")
+            .long("diff")
+            .action(ArgAction::SetTrue)
+            .conflicts_with_all(["dedupe_report", "format"])
+            .help("Print a unified diff between the input and the output instead of the output"),
+    ]
+}
+
+/// Options for naming split files.
+fn split_args() -> Vec<Arg> {
+    vec![
+        Arg::new("split_name")
+            .long("split-name")
+            .num_args(1)
+            .value_name("TEMPLATE")
+            .requires("split_into")
+            .default_value(DEFAULT_SPLIT_TEMPLATE)
+            .help("File name template for --split-into, with placeholders such as {index:03}, {slug}, {title}, {todo} and {property:ID}"),
+        Arg::new("promote")
+            .long("promote")
+            .action(ArgAction::SetTrue)
+            .requires("split_into")
+            .help("Renumber stars so that each split file starts at level 1"),
+        Arg::new("copy_preamble")
+            .long("copy-preamble")
+            .action(ArgAction::SetTrue)
+            .requires("split_into")
+            .help("Copy the text before the first heading into each split file"),
+    ]
+}
+
+/// Accepts any level, like the default parser, but offers the usual ones
+/// to shell completion.
+#[derive(Clone)]
+struct LevelValueParser;
+
+impl TypedValueParser for LevelValueParser {
+    type Value = String;
+
+    fn parse_ref(&self, cmd: &ClapCommand, arg: Option<&Arg>, value: &OsStr) -> Result<String, clap::Error> {
+        StringValueParser::new().parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(["1", "2", "3", "4", "5", "6"].into_iter().map(PossibleValue::new)))
+    }
+}
+
+/// The trailing CMD, required unless one of `unless` is given.
+fn cmd_arg(unless: &[&'static str]) -> Arg {
+    Arg::new("cmd")
+        .num_args(1..)
+        .trailing_var_arg(true)
+        .allow_hyphen_values(true)
+        .required_unless_present_any(unless.iter().copied())
+        .value_name("CMD ...")
+        .value_hint(ValueHint::CommandWithArguments)
+        .help("Command to execute with grouped org sections as input")
+}
+
+fn build_cli() -> ClapCommand {
+    ClapCommand::new("org_grouper")
         .about("Group org-mode sections at a given heading level, separate groups by NUL, pipe to CMD, then optionally replace NULs in CMD output with newlines.")
         .after_help("EXAMPLES:\n  \
             cat notes.org | org_grouper ugrep --null-data \"TODO\"\n  \
             cat notes.org | org_grouper --group-headings-at=2 -- grep -E \"Priority|Important\"\n  \
-            cat notes.org | org_grouper --out-replace-nulls-with=\"\\n---\\n\" -- wc -l")
+            cat notes.org | org_grouper --out-replace-nulls-with=\"\\n---\\n\" -- wc -l\n  \
+            org_grouper list --group-headings-at=2 --input notes.org\n  \
+            org_grouper join inbox.org work.org --under-heading Archive\n\n\
+            Without a subcommand, org_grouper runs CMD like the exec subcommand.\n\
+            A CMD named like a subcommand (e.g. join or grep) must follow --.")
+        .args_conflicts_with_subcommands(true)
+        .args_override_self(true)
+        .subcommand_negates_reqs(true)
         .arg(
-            Arg::new("group_headings_at")
-                .long("group-headings-at")
-                .num_args(1)
-                .value_name("LEVEL")
-                .help("Org heading star level to group at (e.g., 1 for '*', 2 for '**')")
-                .default_value("1"),
+            Arg::new("no_config")
+                .long("no-config")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Ignore config files: no default options and no @aliases"),
         )
+        .args(input_args())
+        .args(input_format_args())
+        .args(exec_args())
+        .args(export_args())
         .arg(
-            Arg::new("out_replace_nulls")
-                .long("out-replace-nulls")
+            Arg::new("split_into")
+                .long("split-into")
                 .num_args(1)
-                .value_name("yes|no")
-                .help("If 'yes', replace NUL (\\0) in CMD output with replacement string")
-                .default_value("yes"),
+                .value_name("DIR")
+                .value_hint(ValueHint::DirPath)
+                .help("Write each group to its own file in DIR instead of running CMD, and print the created paths (same as the split subcommand)"),
         )
-        .arg(
-            Arg::new("out_replace_nulls_with")
-                .long("out-replace-nulls-with")
-                .num_args(1)
-                .value_name("STRING")
-                .help("String to replace NUL (\\0) characters with (supports escape sequences like \\n, \\t)")
-                .default_value("\\n"),
+        .args(split_args())
+        .arg(cmd_arg(&[
+            "update_cookies",
+            "sort_by",
+            "dedupe",
+            "shift_levels",
+            "diff",
+            "split_into",
+            "sh",
+        ]))
+        .mut_arg("diff", |arg| arg.conflicts_with("split_into"))
+        .mut_arg("input_format", |arg| arg.conflicts_with("split_into"))
+        .mut_arg("format", |arg| arg.conflicts_with("split_into"))
+        .mut_arg("heading_regex", |arg| arg.conflicts_with("split_into"))
+        .mut_arg("each", |arg| arg.conflicts_with("split_into"))
+        .subcommand(
+            ClapCommand::new("exec")
+                .about("Pipe the groups to CMD, separated by NUL (the default without a subcommand)")
+                .args(input_args())
+                .args(input_format_args())
+                .args(exec_args())
+                .args(export_args())
+                .arg(cmd_arg(&["sh"])),
         )
-        .arg(
-            Arg::new("cmd")
-                .num_args(1..)
-                .trailing_var_arg(true)
-                .allow_hyphen_values(true)
-                .required(true)
-                .value_name("CMD ...")
-                .help("Command to execute with grouped org sections as input"),
+        .subcommand(
+            ClapCommand::new("grep")
+                .about("Print the groups that match a regular expression")
+                .args(input_args())
+                .args(input_format_args())
+                .arg(
+                    Arg::new("pattern")
+                        .required(true)
+                        .value_name("PATTERN")
+                        .help("Regular expression searched in the whole text of each group"),
+                )
+                .arg(
+                    Arg::new("ignore_case")
+                        .long("ignore-case")
+                        .short('i')
+                        .action(ArgAction::SetTrue)
+                        .help("Match PATTERN case-insensitively"),
+                )
+                .arg(
+                    Arg::new("invert_match")
+                        .long("invert-match")
+                        .short('v')
+                        .action(ArgAction::SetTrue)
+                        .help("Print the groups that do not match"),
+                )
+                .arg(
+                    Arg::new("count")
+                        .long("count")
+                        .short('c')
+                        .action(ArgAction::SetTrue)
+                        .help("Print the number of matching groups instead"),
+                )
+                .args(export_args())
+                .mut_arg("format", |arg| arg.conflicts_with("count")),
+        )
+        .subcommand(
+            ClapCommand::new("list")
+                .about("Print one line per group")
+                .args(input_args())
+                .arg(
+                    Arg::new("template")
+                        .long("template")
+                        .num_args(1)
+                        .value_name("TEMPLATE")
+                        .default_value(DEFAULT_LIST_TEMPLATE)
+                        .help("Line template, with placeholders such as {index}, {line}, {heading}, {title}, {todo}, {tags} and {property:ID}"),
+                ),
+        )
+        .subcommand(
+            ClapCommand::new("outline")
+                .about("Print the headlines of the groups as an indented tree")
+                .args(input_args())
+                .arg(
+                    Arg::new("max_level")
+                        .long("max-level")
+                        .num_args(1)
+                        .value_name("LEVEL")
+                        .value_parser(LevelValueParser)
+                        .hide_possible_values(true)
+                        .help("Leave out headlines deeper than LEVEL"),
+                ),
+        )
+        .subcommand(
+            ClapCommand::new("export")
+                .about("Print the groups as one document, after the rewriting options")
+                .args(input_args())
+                .args(input_format_args())
+                .arg(
+                    Arg::new("diff")
+                        .long("diff")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["dedupe_report", "format"])
+                        .help("Print a unified diff between the input and the output instead of the output"),
+                )
+                .args(export_args()),
+        )
+        .subcommand(
+            ClapCommand::new("split")
+                .about("Write each group to its own file in DIR and print the created paths")
+                .args(input_args())
+                .arg(
+                    Arg::new("split_into")
+                        .required(true)
+                        .value_name("DIR")
+                        .value_hint(ValueHint::DirPath)
+                        .help("Directory to write the files to"),
+                )
+                .args(split_args()),
+        )
+        .subcommand(
+            ClapCommand::new("stats")
+                .about("Print counts of groups, headlines, TODO keywords and checkboxes")
+                .args(input_args()),
+        )
+        .subcommand(
+            ClapCommand::new("join")
+                .about("Combine org files into one document, nesting each under a headline named after its #+TITLE or file name, and print it")
+                .arg(
+                    Arg::new("files")
+                        .num_args(1..)
+                        .required(true)
+                        .value_name("FILE")
+                        .value_hint(ValueHint::FilePath)
+                        .help("Org files to join, in order"),
+                )
+                .arg(
+                    Arg::new("under_heading")
+                        .long("under-heading")
+                        .num_args(1)
+                        .value_name("TITLE")
+                        .help("Nest all file headlines under one headline with this title"),
+                )
+                .arg(
+                    Arg::new("demote")
+                        .long("demote")
+                        .num_args(1)
+                        .value_name("N")
+                        .default_value("0")
+                        .help("Add N levels to every headline of the joined document"),
+                )
+                .arg(
+                    Arg::new("merge_duplicates")
+                        .long("merge-duplicates")
+                        .action(ArgAction::SetTrue)
+                        .help("Merge file headlines with identical titles into one"),
+                ),
+        )
+        .subcommand(
+            ClapCommand::new("archive")
+                .about("Move done subtrees (DONE and CANCELLED, or the done states of #+TODO) from FILE to an archive file, like org-archive-subtree")
+                .arg(
+                    Arg::new("file")
+                        .required(true)
+                        .value_name("FILE")
+                        .value_hint(ValueHint::FilePath)
+                        .help("Org file to archive from; it is rewritten in place"),
+                )
+                .arg(
+                    Arg::new("older_than")
+                        .long("older-than")
+                        .num_args(1)
+                        .value_name("DAYS")
+                        .help("Only archive entries whose CLOSED timestamp is at least DAYS days old"),
+                )
+                .arg(
+                    Arg::new("archive_file")
+                        .long("archive-file")
+                        .num_args(1)
+                        .value_name("PATH")
+                        .value_hint(ValueHint::FilePath)
+                        .help("File to append archived entries to (default: FILE_archive)"),
+                )
+                .arg(
+                    Arg::new("diff")
+                        .long("diff")
+                        .action(ArgAction::SetTrue)
+                        .help("Print a unified diff of FILE and the archive file instead of writing them"),
+                ),
         )
-        .get_matches();
+        .subcommand(
+            ClapCommand::new("refile")
+                .about("Move subtrees whose headline matches a regex under a target heading, like org-refile")
+                .arg(
+                    Arg::new("file")
+                        .required(true)
+                        .value_name("FILE")
+                        .value_hint(ValueHint::FilePath)
+                        .help("Org file to refile from; it is rewritten in place"),
+                )
+                .arg(
+                    Arg::new("select")
+                        .long("select")
+                        .num_args(1)
+                        .required(true)
+                        .value_name("REGEX")
+                        .help("Refile every subtree whose headline matches REGEX"),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .num_args(1)
+                        .required(true)
+                        .value_name("PATH")
+                        .help("Target heading path from the top level, e.g. 'Projects/Backlog'"),
+                )
+                .arg(
+                    Arg::new("target_file")
+                        .long("target-file")
+                        .num_args(1)
+                        .value_name("FILE")
+                        .value_hint(ValueHint::FilePath)
+                        .help("Look up the target heading in this file instead of FILE"),
+                )
+                .arg(
+                    Arg::new("dry_run")
+                        .long("dry-run")
+                        .visible_alias("diff")
+                        .action(ArgAction::SetTrue)
+                        .help("Print a unified diff of the changes instead of writing them"),
+                ),
+        )
+        .subcommand(
+            ClapCommand::new("completions")
+                .about("Print a shell completion script")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .value_name("SHELL")
+                        .value_parser(clap::value_parser!(Shell))
+                        .help("Shell to complete for"),
+                )
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .num_args(1)
+                        .value_name("FILE")
+                        .value_hint(ValueHint::FilePath)
+                        .help("Also complete the heading paths, tags and TODO keywords of this org file"),
+                ),
+        )
+        .subcommand(
+            ClapCommand::new("man")
+                .about("Print the man page in roff format")
+                .arg(
+                    Arg::new("subcommand")
+                        .value_name("SUBCOMMAND")
+                        .help("Print the page of this subcommand instead"),
+                ),
+        )
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let matches = build_cli().get_matches_from(cli_args()?);
+
+    let result = match matches.subcommand() {
+        Some(("join", sub)) => run_join(sub),
+        Some(("archive", sub)) => run_archive(sub),
+        Some(("refile", sub)) => run_refile(sub),
+        Some(("exec", sub)) => run_exec(sub),
+        Some(("grep", sub)) => run_grep(sub),
+        Some(("list", sub)) => run_list(sub),
+        Some(("outline", sub)) => run_outline(sub),
+        Some(("export", sub)) => run_export(sub),
+        Some(("split", sub)) => run_split(sub),
+        Some(("stats", sub)) => run_stats(sub),
+        Some(("completions", sub)) => run_completions(sub),
+        Some(("man", sub)) => run_man(sub),
+        _ => run_pipeline(&matches),
+    };
+    // Like other filters, stop quietly when the reader of our stdout has
+    // gone away, e.g. `org_grouper ... | head`.
+    match result {
+        Err(e) if is_broken_pipe(e.as_ref()) => std::process::exit(EXIT_BROKEN_PIPE),
+        result => result,
+    }
+}
+
+/// The command line with a leading `@alias` expanded and the config
+/// defaults put before the user's own options, which override them.
+fn cli_args() -> Result<Vec<OsString>, Box<dyn std::error::Error>> {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    if std::env::var_os("ORG_GROUPER_NO_CONFIG").is_some_and(|value| !value.is_empty())
+        || args.iter().skip(1).take_while(|arg| *arg != "--").any(|arg| arg == "--no-config")
+    {
+        return Ok(args);
+    }
+
+    let paths = config_paths(
+        &std::env::current_dir()?,
+        std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from),
+        std::env::var_os("HOME").map(PathBuf::from),
+    );
+    let config = load_config(&paths)?;
+    let args = config.expand_alias(args)?;
+
+    let cli = build_cli();
+    let knows = |command: &clap::Command, name: &str| command.get_arguments().any(|arg| arg.get_long() == Some(name));
+    for (name, _) in &config.defaults {
+        if !knows(&cli, name) && !cli.get_subcommands().any(|sub| knows(sub, name)) {
+            return Err(format!("Unknown option '{name}' in the [defaults] of the config").into());
+        }
+    }
+    // Each command gets the defaults it has an option for, right after its
+    // name; the others would be rejected.
+    let first = args.get(1).and_then(|arg| arg.to_str()).unwrap_or("");
+    if first == "help" {
+        return Ok(args);
+    }
+    let (command, at) = match cli.find_subcommand(first) {
+        Some(sub) => (sub, 2),
+        None => (&cli, 1),
+    };
+    let defaults = config
+        .defaults
+        .iter()
+        .filter(|(name, _)| knows(command, name))
+        .flat_map(|(_, args)| args.iter().map(OsString::from));
+
+    let mut with_defaults = Vec::with_capacity(args.len() + config.defaults.len());
+    with_defaults.extend(args[..at].iter().cloned());
+    with_defaults.extend(defaults);
+    with_defaults.extend(args.into_iter().skip(at));
+    Ok(with_defaults)
+}
+
+fn run_join(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let options = JoinOptions {
+        under_heading: matches.get_one::<String>("under_heading").cloned(),
+        demote: matches
+            .get_one::<String>("demote")
+            .unwrap()
+            .parse()
+            .map_err(|_| "Invalid --demote value")?,
+        merge_duplicates: matches.get_flag("merge_duplicates"),
+    };
+
+    let mut documents = Vec::new();
+    for file in matches.get_many::<String>("files").unwrap() {
+        let content = fs::read_to_string(file).map_err(|e| format!("{file}: {e}"))?;
+        let name = Path::new(file)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| file.clone());
+        documents.push((name, content));
+    }
+
+    io::stdout().write_all(join_documents(&documents, &options)?.as_bytes())?;
+    Ok(())
+}
+
+fn run_archive(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let file = matches.get_one::<String>("file").unwrap();
+    let archive_path = matches
+        .get_one::<String>("archive_file")
+        .cloned()
+        .unwrap_or_else(|| format!("{file}_archive"));
+    let older_than_days = matches
+        .get_one::<String>("older_than")
+        .map(|days| days.parse::<i64>())
+        .transpose()
+        .map_err(|_| "Invalid --older-than value")?;
+
+    let input = fs::read_to_string(file).map_err(|e| format!("{file}: {e}"))?;
+    let source_file = fs::canonicalize(file)?.display().to_string();
+    let now = chrono::Local::now();
+    let options = ArchiveOptions {
+        older_than_days,
+        today: now.date_naive(),
+        archive_time: now.format("%Y-%m-%d %a %H:%M").to_string(),
+        category: Path::new(file)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        source_file,
+    };
+
+    let result = archive_done(&input, &options)?;
+    if result.count == 0 {
+        eprintln!("Nothing to archive in {file}");
+        return Ok(());
+    }
+
+    let old_archive = match fs::read_to_string(&archive_path) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("{archive_path}: {e}").into()),
+    };
+    let mut archive = old_archive.clone();
+    if archive.is_empty() {
+        archive.push_str(&archive_file_header(&options.source_file));
+    } else if !archive.ends_with('\n') {
+        archive.push('\n');
+    }
+    archive.push_str(&result.archived);
+
+    if matches.get_flag("diff") {
+        let mut out = io::stdout().lock();
+        for (path, old, new) in [(file, &input, &result.remaining), (&archive_path, &old_archive, &archive)] {
+            write!(out, "{}", unified_diff(old, new, &format!("a/{path}"), &format!("b/{path}")))?;
+        }
+        return Ok(());
+    }
+
+    // Write the archive first, so a failure never loses entries.
+    fs::write(&archive_path, archive).map_err(|e| format!("{archive_path}: {e}"))?;
+    fs::write(file, &result.remaining)?;
+
+    eprintln!("Archived {} entries from {file} to {archive_path}", result.count);
+    Ok(())
+}
+
+fn run_refile(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let file = matches.get_one::<String>("file").unwrap();
+    let select = Regex::new(matches.get_one::<String>("select").unwrap())?;
+    let to = matches.get_one::<String>("to").unwrap();
+    // A target file that is FILE itself is an in-file refile; two writes to
+    // one path would lose the refiled subtrees.
+    let target_file = matches
+        .get_one::<String>("target_file")
+        .filter(|path| !same_file(path, file));
+
+    let source = fs::read_to_string(file).map_err(|e| format!("{file}: {e}"))?;
+    let target = target_file
+        .map(|path| fs::read_to_string(path).map_err(|e| format!("{path}: {e}")))
+        .transpose()?;
+
+    let result = refile(&source, target.as_deref(), &select, to)?;
+    if result.count == 0 {
+        eprintln!("No headline matches '{select}' in {file}");
+        return Ok(());
+    }
+
+    let mut changes = vec![(file, &source, result.source)];
+    if let (Some(path), Some(old), Some(new)) = (target_file, &target, result.target) {
+        changes.push((path, old, new));
+    }
+
+    if matches.get_flag("dry_run") {
+        let mut out = io::stdout().lock();
+        for (path, old, new) in &changes {
+            write!(out, "{}", unified_diff(old, new, &format!("a/{path}"), &format!("b/{path}")))?;
+        }
+        return Ok(());
+    }
+
+    // Write the target first, so a failure never loses the refiled subtrees.
+    for (path, _, new) in changes.iter().rev() {
+        fs::write(path, new).map_err(|e| format!("{path}: {e}"))?;
+    }
+    eprintln!("Refiled {} subtrees under '{to}'", result.count);
+    Ok(())
+}
+
+/// Whether the paths `a` and `b` name the same existing file.
+fn same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
 
+fn run_completions(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let shell = *matches.get_one::<Shell>("shell").unwrap();
+    let mut cli = build_cli();
+    if let Some(file) = matches.get_one::<String>("from") {
+        let content = fs::read_to_string(file).map_err(|e| format!("{file}: {e}"))?;
+        let values = CompletionValues::from_document(&content);
+        let mut headline_values = values.keywords;
+        headline_values.extend(values.tags.iter().map(|tag| format!(":{tag}:")));
+        cli = suggest_values(cli, "to", &values.heading_paths);
+        cli = suggest_values(cli, "select", &headline_values);
+        cli = suggest_values(cli, "pattern", &headline_values);
+    }
+    clap_complete::generate(shell, &mut cli, "org_grouper", &mut io::stdout());
+    Ok(())
+}
+
+/// Makes the completion script offer `values` for every argument `id` of
+/// `cli` and its subcommands. Only for generating scripts: the arguments
+/// would reject anything else.
+fn suggest_values(cli: ClapCommand, id: &str, values: &[String]) -> ClapCommand {
+    let names: Vec<String> = cli.get_subcommands().map(|sub| sub.get_name().to_string()).collect();
+    let mut cli = cli;
+    if cli.get_arguments().any(|arg| arg.get_id() == id) {
+        cli = cli.mut_arg(id, |arg| arg.value_parser(PossibleValuesParser::new(values)));
+    }
+    for name in names {
+        cli = cli.mut_subcommand(name, |sub| suggest_values(sub, id, values));
+    }
+    cli
+}
+
+fn run_man(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let cli = build_cli();
+    let page = match matches.get_one::<String>("subcommand") {
+        Some(name) => {
+            let sub = cli
+                .find_subcommand(name)
+                .ok_or_else(|| format!("Unknown subcommand '{name}'"))?;
+            sub.clone().name(format!("org_grouper-{name}"))
+        }
+        None => cli,
+    };
+    clap_mangen::Man::new(page).render(&mut io::stdout())?;
+    Ok(())
+}
+
+/// The input after the rewriting options, split into groups.
+struct Prepared {
+    /// Name of the input for diffs: its path, or `<stdin>`.
+    name: String,
+    original: String,
+    input: String,
+    groups: Vec<String>,
+    /// The line each group starts on in `input`.
+    lines: Vec<usize>,
+    /// Whether the first group is the text before the first heading.
+    preamble: bool,
+}
+
+impl Prepared {
+    /// The `{index}` of group `index`: sections count from 1, as the files
+    /// `split` writes do, and the text before the first heading is 0.
+    fn section_index(&self, index: usize) -> usize {
+        index + usize::from(!self.preamble)
+    }
+}
+
+/// Reads the input and applies the shared input and grouping options.
+/// Returns `None` when `--dedupe-report` has already printed the answer.
+fn prepare_input(matches: &ArgMatches) -> Result<Option<Prepared>, Box<dyn std::error::Error>> {
     let level: usize = matches
         .get_one::<String>("group_headings_at")
         .unwrap()
         .parse()
         .map_err(|_| "Invalid --group-headings-at value")?;
 
+    let (name, original) = match matches.get_one::<String>("input") {
+        Some(path) => (
+            path.clone(),
+            fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?,
+        ),
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            ("<stdin>".to_string(), input)
+        }
+    };
+    let mut input = original.clone();
+
+    // Only some commands take --input-format and --heading-regex; the rest
+    // read org.
+    let format: InputFormat = match matches.try_get_one::<String>("input_format") {
+        Ok(Some(format)) => format.parse()?,
+        _ => InputFormat::Org,
+    };
+    let heading_regex = match matches.try_get_one::<String>("heading_regex") {
+        Ok(Some(pattern)) => Some(RegexHeadings::new(pattern)?),
+        _ => None,
+    };
+    if format != InputFormat::Org || heading_regex.is_some() {
+        for (id, option) in [
+            ("update_cookies", "--update-cookies"),
+            ("sort_by", "--sort-by"),
+            ("shift_levels", "--shift-levels"),
+        ] {
+            if matches.value_source(id) == Some(ValueSource::CommandLine) {
+                return Err(format!("{option} only works on org input").into());
+            }
+        }
+    }
+    let detector = || -> Box<dyn HeadingDetector> {
+        match &heading_regex {
+            Some(detector) => Box::new(detector.clone()),
+            None => format.detector(),
+        }
+    };
+    let group = |input: &str| group_sections(input, level, detector().as_mut());
+
+    if matches.get_flag("update_cookies") {
+        input = update_cookies(&input);
+    }
+
+    if let Some(key) = matches.get_one::<String>("sort_by") {
+        let key: SortKey = key.parse()?;
+        input = sort_sections(&input, level, &key, matches.get_flag("reverse"))?;
+    }
+
+    if let Some(mode) = matches.get_one::<String>("dedupe") {
+        let mode: DedupeMode = mode.parse()?;
+        if matches.get_flag("dedupe_report") {
+            // --update-cookies keeps every line where it is, and --sort-by
+            // is refused, so the numbers point into the original input.
+            let groups = group(&input);
+            let lines = group_line_numbers(&groups);
+            let mut out = io::stdout().lock();
+            for cluster in duplicate_clusters(&groups, mode) {
+                let numbers: Vec<String> = cluster.iter().map(|&i| lines[i].to_string()).collect();
+                let first_line = groups[cluster[0]].lines().next().unwrap_or("");
+                writeln!(out, "lines {}: {}", numbers.join(", "), first_line)?;
+            }
+            return Ok(None);
+        }
+        input = dedupe_groups(group(&input), mode).concat();
+    }
+
+    if let Some(delta) = matches.get_one::<String>("shift_levels") {
+        let delta: isize = delta.parse().map_err(|_| "Invalid --shift-levels value")?;
+        let select = matches
+            .get_one::<String>("select")
+            .map(|pattern| Regex::new(pattern))
+            .transpose()?;
+        input = shift_sections(&input, level, delta, select.as_ref())?;
+    }
+
+    let groups = group(&input);
+    let lines = group_line_numbers(&groups);
+    let incomplete_only = matches.get_flag("incomplete_checkboxes");
+    let (groups, lines): (Vec<String>, Vec<usize>) = groups
+        .into_iter()
+        .zip(lines)
+        .filter(|(group, _)| !incomplete_only || has_incomplete_checkboxes(group))
+        .unzip();
+    // Only the first group can be the text before the first heading.
+    let preamble = groups
+        .first()
+        .is_some_and(|first| lines[0] == 1 && !starts_with_heading(first, detector().as_mut()));
+
+    Ok(Some(Prepared {
+        name,
+        original,
+        input,
+        groups,
+        lines,
+        preamble,
+    }))
+}
+
+/// The main command without a subcommand: `exec`, or `split` with
+/// `--split-into`.
+fn run_pipeline(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    if matches.get_one::<String>("split_into").is_some() {
+        return run_split(matches);
+    }
+    run_exec(matches)
+}
+
+fn run_split(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(prepared) = prepare_input(matches)? else {
+        return Ok(());
+    };
+    let dir = matches.get_one::<String>("split_into").unwrap();
+    let options = SplitOptions {
+        template: matches.get_one::<String>("split_name").unwrap().clone(),
+        promote: matches.get_flag("promote"),
+        copy_preamble: matches.get_flag("copy_preamble"),
+    };
+    let keywords = TodoKeywords::from_document(&prepared.input);
+    let files = split_groups(&prepared.groups, preamble(&prepared.input), &keywords, &options)?;
+    write_split_files(Path::new(dir), &files)
+}
+
+fn run_export(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(prepared) = prepare_input(matches)? else {
+        return Ok(());
+    };
+    print_groups(&prepared, matches.get_flag("diff"), &Exporter::new(matches, &prepared)?)?;
+    Ok(())
+}
+
+/// Converts org text to the `--format` of a command.
+struct Exporter {
+    format: ExportFormat,
+    keywords: TodoKeywords,
+    options: ExportOptions,
+}
+
+impl Exporter {
+    fn new(matches: &ArgMatches, prepared: &Prepared) -> Result<Self, Box<dyn std::error::Error>> {
+        let format: ExportFormat = matches.get_one::<String>("format").unwrap().parse()?;
+        let org_input = matches.get_one::<String>("input_format").is_none_or(|format| format == "org")
+            && matches.get_one::<String>("heading_regex").is_none();
+        if format != ExportFormat::Org && !org_input {
+            return Err("--format only converts org input".into());
+        }
+        Ok(Exporter {
+            format,
+            keywords: TodoKeywords::from_document(&prepared.input),
+            options: ExportOptions {
+                properties: matches.get_one::<String>("properties").unwrap().parse()?,
+                title: document_title(&prepared.input),
+            },
+        })
+    }
+
+    fn export(&self, text: &str) -> String {
+        export(text, self.format, &self.keywords, &self.options)
+    }
+}
+
+/// Prints the groups as one document, or its diff against the input.
+fn print_groups(prepared: &Prepared, diff: bool, exporter: &Exporter) -> io::Result<()> {
+    let result = exporter.export(&prepared.groups.concat());
+    if diff {
+        print_diff(&prepared.name, &prepared.original, &result)
+    } else {
+        io::stdout().write_all(result.as_bytes())
+    }
+}
+
+fn run_grep(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let pattern = RegexBuilder::new(matches.get_one::<String>("pattern").unwrap())
+        .case_insensitive(matches.get_flag("ignore_case"))
+        .multi_line(true)
+        .build()?;
+    let Some(prepared) = prepare_input(matches)? else {
+        return Ok(());
+    };
+    let exporter = Exporter::new(matches, &prepared)?;
+    let invert = matches.get_flag("invert_match");
+    let matching: Vec<&str> = prepared
+        .groups
+        .iter()
+        .map(String::as_str)
+        .filter(|group| pattern.is_match(group) != invert)
+        .collect();
+
+    let mut out = io::stdout().lock();
+    if matches.get_flag("count") {
+        writeln!(out, "{}", matching.len())?;
+    } else {
+        out.write_all(exporter.export(&matching.concat()).as_bytes())?;
+    }
+    out.flush()?;
+    // Like grep, fail when nothing matched.
+    if matching.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_list(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(prepared) = prepare_input(matches)? else {
+        return Ok(());
+    };
+    let template = matches.get_one::<String>("template").unwrap();
+    let keywords = TodoKeywords::from_document(&prepared.input);
+
+    let mut out = io::stdout().lock();
+    for (index, (group, &line)) in prepared.groups.iter().zip(&prepared.lines).enumerate() {
+        // The text before the first headline is not a section.
+        if heading_level(group).is_none() {
+            continue;
+        }
+        let fields = SectionFields::new(group, prepared.section_index(index), line, &keywords);
+        writeln!(out, "{}", render_template(template, &fields, str::to_string)?)?;
+    }
+    Ok(())
+}
+
+fn run_outline(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let max_level: Option<usize> = matches
+        .get_one::<String>("max_level")
+        .map(|level| level.parse().map_err(|_| "Invalid --max-level value"))
+        .transpose()?;
+    let Some(prepared) = prepare_input(matches)? else {
+        return Ok(());
+    };
+
+    let mut out = io::stdout().lock();
+    for group in &prepared.groups {
+        for span in heading_spans(group) {
+            if max_level.is_some_and(|max| span.level > max) {
+                continue;
+            }
+            let headline = group[span.start..span.body_start].trim_end();
+            let text = headline.trim_start_matches('*').trim_start();
+            writeln!(out, "{}{text}", "  ".repeat(span.level - 1))?;
+        }
+    }
+    Ok(())
+}
+
+fn run_stats(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(prepared) = prepare_input(matches)? else {
+        return Ok(());
+    };
+    let keywords = TodoKeywords::from_document(&prepared.input);
+    let stats = document_stats(&prepared.groups, &keywords);
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "groups: {}", stats.groups)?;
+    writeln!(out, "headings: {}", stats.headings())?;
+    for (level, count) in stats.headings_per_level.iter().enumerate() {
+        writeln!(out, "  level {}: {count}", level + 1)?;
+    }
+    for (keyword, count) in &stats.keywords {
+        writeln!(out, "{keyword}: {count}")?;
+    }
+    writeln!(
+        out,
+        "checkboxes: {}/{} ({}%)",
+        stats.checkboxes.checked,
+        stats.checkboxes.total,
+        stats.checkboxes.percent()
+    )?;
+    Ok(())
+}
+
+fn run_exec(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(prepared) = prepare_input(matches)? else {
+        return Ok(());
+    };
+    let diff = matches.get_flag("diff");
+    let exporter = Exporter::new(matches, &prepared)?;
+
     let out_replace_nulls = matches
         .get_one::<String>("out_replace_nulls")
         .map(|s| matches!(s.as_str(), "yes" | "true" | "1"))
@@ -63,47 +1156,369 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .clone();
     
     // Process escape sequences in the replacement string
-    let processed_replacement = process_escape_sequences(&replacement_string);
+    let processed_replacement = process_escape_sequences(&replacement_string)?;
 
     let cmd_parts: Vec<String> = matches
         .get_many::<String>("cmd")
-        .unwrap()
-        .map(|s| s.to_string())
-        .collect();
+        .map(|values| values.map(|s| s.to_string()).collect())
+        .unwrap_or_default();
+
+    let script = matches.get_one::<String>("sh");
+    if script.is_none() && cmd_parts.is_empty() {
+        print_groups(&prepared, diff, &exporter)?;
+        return Ok(());
+    }
+    let groups: Vec<String> = prepared.groups.iter().map(|group| exporter.export(group)).collect();
+    let shell = matches.get_one::<String>("shell").unwrap();
+
+    let framing = match matches.get_one::<String>("in_separator") {
+        Some(separator) => Framing::Separator(process_escape_sequences(separator)?),
+        None => matches.get_one::<String>("framing").unwrap().parse()?,
+    };
+    let out_separator = matches
+        .get_one::<String>("out_separator")
+        .map(|separator| process_escape_sequences(separator))
+        .transpose()?;
+    let err_replace_nulls = matches
+        .get_one::<String>("err_replace_nulls")
+        .map_or(out_replace_nulls, |s| matches!(s.as_str(), "yes" | "true" | "1"));
+    let err_prefix = matches
+        .get_one::<String>("err_prefix")
+        .map(|prefix| process_escape_sequences(prefix))
+        .transpose()?;
+    let duration_arg = |name: &str| {
+        matches
+            .get_one::<String>(name)
+            .map(|value| parse_duration(value))
+            .transpose()
+    };
+    let run_deadline = duration_arg("timeout")?.map(|timeout| Instant::now() + timeout);
+    let section_timeout = duration_arg("section_timeout")?;
+    // Few commands answer in netstrings, so their output is only decoded
+    // when --out-replace-nulls=yes asks for it.
+    let decode_output = match (&out_separator, &framing) {
+        (None, Framing::Netstring) => {
+            out_replace_nulls && matches.value_source("out_replace_nulls") == Some(ValueSource::CommandLine)
+        }
+        _ => out_replace_nulls,
+    };
+    let settings = ChildSettings {
+        output_framing: decode_output
+            .then(|| out_separator.map_or(framing.clone(), Framing::Separator)),
+        replacement: processed_replacement.clone(),
+        stderr_replacement: err_replace_nulls.then_some(processed_replacement),
+        collect: diff,
+        kill_after: duration_arg("kill_after")?.unwrap(),
+        limits: ResourceLimits {
+            cpu_seconds: matches
+                .get_one::<String>("limit_cpu")
+                .map(|value| value.parse().map_err(|_| "Invalid --limit-cpu value"))
+                .transpose()?,
+            memory_bytes: matches
+                .get_one::<String>("limit_memory")
+                .map(|value| parse_size(value))
+                .transpose()?,
+        },
+    };
+    let remaining = || run_deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
 
-    let (cmd_prog, cmd_args) = cmd_parts
-        .split_first()
-        .ok_or("Missing CMD to execute")?;
+    let mut collected = Vec::new();
+    let code = if matches.get_flag("each") {
+        let keywords = TodoKeywords::from_document(&prepared.input);
+        let lines = &prepared.lines;
+        let mut code = 0;
+        for (index, group) in groups.iter().enumerate() {
+            // The text before the first headline has no title, tags and so on.
+            let fields =
+                SectionFields::new(&prepared.groups[index], prepared.section_index(index), lines[index], &keywords)
+                    .empty_when_missing();
+            let argv = match script {
+                Some(script) => vec![
+                    shell.clone(),
+                    "-c".to_string(),
+                    render_template(script, &fields, shell_quote)?,
+                ],
+                None => cmd_parts
+                    .iter()
+                    .map(|part| render_known_placeholders(part, &fields, str::to_string))
+                    .collect::<Result<_, _>>()?,
+            };
+            let prefix = err_prefix
+                .as_deref()
+                .map(|prefix| render_template(prefix, &fields, str::to_string).map(|prefix| (0, prefix)))
+                .into_iter()
+                .collect::<Result<_, _>>()?;
+            let timeout = match (remaining(), section_timeout) {
+                (Some(run), Some(section)) => Some(run.min(section)),
+                (run, section) => run.or(section),
+            };
+            let outcome = run_child(&argv, group.as_bytes().to_vec(), prefix, timeout, &settings)?;
+            collected.extend(outcome.output);
+            if outcome.timed_out {
+                eprintln!("org_grouper: CMD timed out on the group at line {}", lines[index]);
+                code = EXIT_TIMED_OUT;
+                if remaining().is_some_and(|left| left.is_zero()) {
+                    break;
+                }
+                continue;
+            }
+            if !outcome.status.success() {
+                code = exit_code(outcome.status);
+            }
+            // A section killed by a signal, e.g. by Ctrl-C, ends the run.
+            if outcome.status.code().is_none() {
+                break;
+            }
+        }
+        code
+    } else {
+        let argv = match script {
+            Some(script) => vec![shell.clone(), "-c".to_string(), script.clone()],
+            None => cmd_parts,
+        };
+        // The prefix names the group that CMD is being fed.
+        let mut prefixes = Vec::new();
+        if let Some(prefix) = err_prefix.as_deref() {
+            let keywords = TodoKeywords::from_document(&prepared.input);
+            let offsets = group_offsets(&groups, &framing);
+            for (index, offset) in offsets.into_iter().enumerate() {
+                let fields = SectionFields::new(&prepared.groups[index], prepared.section_index(index), prepared.lines[index], &keywords)
+                    .empty_when_missing();
+                prefixes.push((offset, render_template(prefix, &fields, str::to_string)?));
+            }
+        }
+        let outcome = run_child(&argv, frame_groups(&groups, &framing)?, prefixes, remaining(), &settings)?;
+        collected = outcome.output;
+        if outcome.timed_out {
+            eprintln!("org_grouper: CMD timed out");
+            EXIT_TIMED_OUT
+        } else {
+            exit_code(outcome.status)
+        }
+    };
 
-    let mut input = String::new();
-    io::stdin().read_to_string(&mut input)?;
+    if diff {
+        print_diff(&prepared.name, &prepared.original, &String::from_utf8_lossy(&collected))?;
+    }
+
+    std::process::exit(code);
+}
 
-    let groups = group_org_sections(&input, level)?;
+/// How the output of each child is passed on.
+struct ChildSettings {
+    /// Framing of CMD's stdout to translate back, if any.
+    output_framing: Option<Framing>,
+    replacement: String,
+    stderr_replacement: Option<String>,
+    /// Return stdout instead of writing it.
+    collect: bool,
+    /// Grace period between `SIGTERM` and `SIGKILL` on a timeout.
+    kill_after: Duration,
+    limits: ResourceLimits,
+}
 
-    let grouped = groups.join("\0");
-    let mut child = Command::new(OsStr::new(cmd_prog))
-        .args(cmd_args.iter().map(|s| OsStr::new(s)))
+struct ChildOutcome {
+    status: ExitStatus,
+    /// The translated stdout, with `ChildSettings::collect`.
+    output: Vec<u8>,
+    timed_out: bool,
+}
+
+/// Runs `argv` with `input` on its stdin, streaming its stdout and stderr
+/// to ours while it runs, and terminates it after `timeout`.
+///
+/// `err_prefixes` pairs offsets in `input` with the prefix of the stderr
+/// lines written while CMD is fed the input from that offset on.
+fn run_child(
+    argv: &[String],
+    input: Vec<u8>,
+    err_prefixes: Vec<(usize, String)>,
+    timeout: Option<Duration>,
+    settings: &ChildSettings,
+) -> Result<ChildOutcome, Box<dyn std::error::Error>> {
+    let (program, args) = argv.split_first().ok_or("Empty command")?;
+    let mut command = Command::new(OsStr::new(program));
+    command
+        .args(args.iter().map(OsStr::new))
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
+        .stderr(Stdio::piped());
+    // With a timeout, the child leads its own process group, so that a
+    // shell pipeline is terminated as a whole.
+    let group = timeout.is_some();
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        if group {
+            command.process_group(0);
+        }
+        let limits = settings.limits;
+        if !limits.is_empty() {
+            // SAFETY: `apply` only calls setrlimit, which is async-signal-safe.
+            unsafe {
+                command.pre_exec(move || limits.apply());
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    if group || !settings.limits.is_empty() {
+        return Err("Timeouts and resource limits are only supported on Unix".into());
+    }
+    let mut child = command.spawn().map_err(|e| format!("{program}: {e}"))?;
+    #[cfg(unix)]
+    let target = ChildTarget::new(child.id(), group)?;
+    #[cfg(unix)]
+    let forwarder = SignalForwarder::new(target.clone())?;
+    #[cfg(unix)]
+    let watchdog = timeout
+        .map(|timeout| Watchdog::new(target.clone(), timeout, settings.kill_after))
+        .transpose()?;
+
+    // Feed stdin and drain stderr on their own threads, so that a child
+    // that writes before it has read all of its input cannot deadlock us.
+    let mut stdin = child.stdin.take().ok_or("Failed to open CMD stdin")?;
+    let (offsets, prefixes): (Vec<usize>, Vec<String>) = err_prefixes.into_iter().unzip();
+    let fed = Arc::new(AtomicUsize::new(0));
+    let writer_fed = Arc::clone(&fed);
+    let writer = thread::spawn(move || {
+        let mut write = || {
+            let mut start = 0;
+            for (index, &end) in offsets.iter().enumerate().skip(1) {
+                stdin.write_all(&input[start..end])?;
+                writer_fed.store(index, Ordering::Relaxed);
+                start = end;
+            }
+            stdin.write_all(&input[start..])
+        };
+        match write() {
+            // The child may exit without reading everything, like `echo`.
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => result,
+        }
+    });
+    let mut child_stderr = child.stderr.take().ok_or("Failed to open CMD stderr")?;
+    let stderr_replacement = settings.stderr_replacement.clone();
+    let mut stderr_prefixer = (!prefixes.is_empty()).then(|| LinePrefixer::new(""));
+    let stderr_copier = thread::spawn(move || -> io::Result<()> {
+        let mut buffer = [0; 8192];
+        loop {
+            let read = match child_stderr.read(&mut buffer) {
+                Ok(0) => return Ok(()),
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let mut chunk = match &stderr_replacement {
+                Some(replacement) => replace_nulls_in_bytes(&buffer[..read], replacement),
+                None => buffer[..read].to_vec(),
+            };
+            if let Some(prefixer) = stderr_prefixer.as_mut() {
+                prefixer.set_prefix(&prefixes[fed.load(Ordering::Relaxed)]);
+                chunk = prefixer.feed(&chunk);
+            }
+            let mut stderr = io::stderr().lock();
+            stderr.write_all(&chunk)?;
+            stderr.flush()?;
+        }
+    });
+
+    let translator = settings
+        .output_framing
+        .clone()
+        .map(|framing| OutputTranslator::new(framing, &settings.replacement));
+    let mut child_stdout = child.stdout.take().ok_or("Failed to open CMD stdout")?;
+    let collected = copy_stdout(&mut child_stdout, translator, settings.collect)?;
 
+    writer.join().map_err(|_| "CMD stdin writer panicked")??;
+    stderr_copier.join().map_err(|_| "CMD stderr reader panicked")??;
+    // Stop signalling the child before reaping it, after which its pid
+    // may belong to another process.
+    #[cfg(unix)]
     {
-        let stdin = child.stdin.as_mut().ok_or("Failed to open CMD stdin")?;
-        stdin.write_all(grouped.as_bytes())?;
+        target.wait_for_exit()?;
+        target.stop();
     }
+    let status = child.wait()?;
+    #[cfg(unix)]
+    drop(forwarder);
+    #[cfg(unix)]
+    let timed_out = watchdog.is_some_and(Watchdog::finish);
+    #[cfg(not(unix))]
+    let timed_out = false;
 
-    let output = child.wait_with_output()?;
+    Ok(ChildOutcome {
+        status,
+        output: collected,
+        timed_out,
+    })
+}
 
-    let mut stdout = output.stdout;
-    let mut stderr = output.stderr;
-    if out_replace_nulls {
-        stdout = replace_nulls_in_bytes(&stdout, &processed_replacement);
-        stderr = replace_nulls_in_bytes(&stderr, &processed_replacement);
+/// Copies CMD's stdout to ours chunk by chunk, through `translator` when
+/// given. With `collect`, the output is returned instead of written.
+fn copy_stdout(
+    child_stdout: &mut impl Read,
+    mut translator: Option<OutputTranslator>,
+    collect: bool,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut collected = Vec::new();
+    let mut stdout = io::stdout().lock();
+    let mut buffer = [0; 8192];
+    loop {
+        let read = match child_stdout.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let chunk = match translator.as_mut() {
+            Some(translator) => translator.feed(&buffer[..read])?,
+            None => buffer[..read].to_vec(),
+        };
+        if collect {
+            collected.extend_from_slice(&chunk);
+        } else {
+            stdout.write_all(&chunk)?;
+            stdout.flush()?;
+        }
     }
+    if let Some(translator) = translator {
+        let rest = translator.finish()?;
+        if collect {
+            collected.extend_from_slice(&rest);
+        } else {
+            stdout.write_all(&rest)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(collected)
+}
+
+fn print_diff(name: &str, original: &str, result: &str) -> io::Result<()> {
+    let diff = unified_diff(original, result, &format!("a/{name}"), &format!("b/{name}"));
+    io::stdout().write_all(diff.as_bytes())
+}
 
-    io::stdout().write_all(&stdout)?;
-    io::stderr().write_all(&stderr)?;
+fn write_split_files(dir: &Path, files: &[SplitFile]) -> Result<(), Box<dyn std::error::Error>> {
+    // Refuse to overwrite anything before writing the first file.
+    for file in files {
+        let path = dir.join(&file.path);
+        if path.exists() {
+            return Err(format!("Refusing to overwrite existing file {}", path.display()).into());
+        }
+    }
 
-    std::process::exit(output.status.code().unwrap_or(1));
-}
\ No newline at end of file
+    let mut out = io::stdout().lock();
+    for file in files {
+        let path = dir.join(&file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?
+            .write_all(file.content.as_bytes())?;
+        writeln!(out, "{}", path.display())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,307 @@
+use crate::export::{link_title, plain_text, Block, Checkbox, Document, ExportOptions, Inline, ListItem, PropertyMode};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Renders `document` as CommonMark, using GitHub's extensions for tables
+/// and strikethrough.
+pub fn to_markdown(document: &Document, options: &ExportOptions) -> String {
+    let mut output = String::new();
+    if options.properties == PropertyMode::FrontMatter {
+        let entries = document.front_matter();
+        if !entries.is_empty() {
+            output.push_str("---\n");
+            for (key, value) in entries {
+                output.push_str(&format!("{key}: {}\n", yaml_string(value)));
+            }
+            output.push_str("---\n\n");
+        }
+    }
+    output.push_str(&render_blocks(&document.blocks, &Anchors::new(&document.blocks)));
+    output
+}
+
+/// GitHub's anchor and the plain title of every headline, in document
+/// order.
+struct Anchors {
+    ids: Vec<String>,
+    titles: Vec<String>,
+}
+
+impl Anchors {
+    fn new(blocks: &[Block]) -> Self {
+        let mut anchors = Anchors {
+            ids: Vec::new(),
+            titles: Vec::new(),
+        };
+        for block in blocks {
+            let Block::Heading {
+                keyword,
+                priority,
+                title,
+                tags,
+                ..
+            } = block
+            else {
+                continue;
+            };
+            // GitHub makes the anchor from all of the heading's text.
+            let title = plain_text(title);
+            let mut text: Vec<String> = keyword.iter().cloned().collect();
+            text.extend(priority.map(|priority| format!("[#{priority}]")));
+            text.push(title.clone());
+            if !tags.is_empty() {
+                text.push(format!(":{}:", tags.join(":")));
+            }
+            let id = heading_anchor(&text.join(" "));
+            anchors.ids.push(unique_anchor(&anchors.ids, id));
+            anchors.titles.push(title);
+        }
+        anchors
+    }
+
+    /// The anchor of the headline with the plain title `title`.
+    fn find(&self, title: &str) -> Option<&str> {
+        let index = self.titles.iter().position(|known| known == title)?;
+        Some(&self.ids[index])
+    }
+}
+
+fn yaml_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Renders blocks separated by blank lines.
+fn render_blocks(blocks: &[Block], anchors: &Anchors) -> String {
+    blocks
+        .iter()
+        .map(|block| render_block(block, anchors))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_block(block: &Block, anchors: &Anchors) -> String {
+    match block {
+        Block::Heading {
+            level,
+            keyword,
+            priority,
+            title,
+            tags,
+            ..
+        } => {
+            let mut text = format!("{} ", "#".repeat((*level).clamp(1, 6)));
+            if let Some(keyword) = keyword {
+                text.push_str(keyword);
+                text.push(' ');
+            }
+            if let Some(priority) = priority {
+                text.push_str(&format!("\\[#{priority}\\] "));
+            }
+            text.push_str(&render_inlines(title, anchors));
+            if !tags.is_empty() {
+                text.push_str(&escape_text(&format!(" :{}:", tags.join(":"))));
+            }
+            format!("{}\n", text.trim_end())
+        }
+        Block::Paragraph(inlines) => format!("{}\n", escape_line_starts(&render_inlines(inlines, anchors))),
+        Block::List { ordered, items } => render_list(*ordered, items, anchors),
+        Block::Table { rows, .. } => render_table(rows, anchors),
+        Block::Code { language, code } => {
+            let longest = longest_run(code, '`');
+            let fence = "`".repeat(longest.max(2) + 1);
+            format!("{fence}{}\n{code}{fence}\n", language.as_deref().unwrap_or(""))
+        }
+        Block::Quote(blocks) => prefix_lines(&render_blocks(blocks, anchors), "> ", ">"),
+        Block::Raw { format, text } if matches!(format.as_str(), "md" | "markdown" | "html") => text.clone(),
+        Block::Raw { .. } => String::new(),
+        Block::Rule => "---\n".to_string(),
+    }
+}
+
+fn render_list(ordered: bool, items: &[ListItem], anchors: &Anchors) -> String {
+    let mut output = String::new();
+    for (index, item) in items.iter().enumerate() {
+        let mut marker = if ordered {
+            format!("{}. ", index + 1)
+        } else {
+            "- ".to_string()
+        };
+        let width = marker.len();
+        match item.checkbox {
+            Some(Checkbox::Checked) => marker.push_str("[x] "),
+            Some(Checkbox::Unchecked | Checkbox::Partial) => marker.push_str("[ ] "),
+            None => {}
+        }
+        if let Some(term) = &item.term {
+            marker.push_str(&format!("**{}**: ", render_inlines(term, anchors)));
+        }
+
+        // A leading paragraph goes on the marker line; the other blocks are
+        // indented to the item's content column.
+        let mut blocks = &item.content[..];
+        let mut body = String::new();
+        if let Some((Block::Paragraph(inlines), rest)) = blocks.split_first() {
+            body = escape_line_starts(&render_inlines(inlines, anchors));
+            blocks = rest;
+        }
+        for block in blocks {
+            // Nested lists stay tight under the item's text.
+            if !body.is_empty() {
+                body.push_str(if matches!(block, Block::List { .. }) { "\n" } else { "\n\n" });
+            }
+            body.push_str(render_block(block, anchors).trim_end_matches('\n'));
+        }
+        let mut lines = body.lines();
+        output.push_str(format!("{marker}{}", lines.next().unwrap_or("")).trim_end());
+        output.push('\n');
+        output.push_str(&prefix_lines(&lines.collect::<Vec<_>>().join("\n"), &" ".repeat(width), ""));
+    }
+    output
+}
+
+fn render_table(rows: &[Vec<Vec<Inline>>], anchors: &Anchors) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let render_row = |row: &[Vec<Inline>]| {
+        let mut line = String::from("|");
+        for column in 0..columns {
+            let cell = row.get(column).map(|cell| render_inlines(cell, anchors)).unwrap_or_default();
+            line.push_str(&format!(" {} |", cell.replace('|', "\\|")));
+        }
+        line.push('\n');
+        line
+    };
+
+    // Markdown tables always have a header row, so the first row is it.
+    let mut output = String::new();
+    for (index, row) in rows.iter().enumerate() {
+        output.push_str(&render_row(row));
+        if index == 0 {
+            output.push('|');
+            output.push_str(&" --- |".repeat(columns));
+            output.push('\n');
+        }
+    }
+    output
+}
+
+fn prefix_lines(text: &str, prefix: &str, blank_prefix: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                format!("{blank_prefix}\n")
+            } else {
+                format!("{prefix}{line}\n")
+            }
+        })
+        .collect()
+}
+
+fn longest_run(text: &str, c: char) -> usize {
+    text.split(|x| x != c).map(str::len).max().unwrap_or(0)
+}
+
+fn render_inlines(inlines: &[Inline], anchors: &Anchors) -> String {
+    inlines.iter().map(|inline| render_inline(inline, anchors)).collect()
+}
+
+fn render_inline(inline: &Inline, anchors: &Anchors) -> String {
+    match inline {
+        Inline::Text(text) => escape_text(text),
+        Inline::Bold(inner) => format!("**{}**", render_inlines(inner, anchors)),
+        Inline::Italic(inner) => format!("*{}*", render_inlines(inner, anchors)),
+        Inline::Underline(inner) => format!("<u>{}</u>", render_inlines(inner, anchors)),
+        Inline::Strike(inner) => format!("~~{}~~", render_inlines(inner, anchors)),
+        Inline::Code(code) => {
+            let fence = "`".repeat(longest_run(code, '`') + 1);
+            if code.starts_with('`') || code.ends_with('`') {
+                format!("{fence} {code} {fence}")
+            } else {
+                format!("{fence}{code}{fence}")
+            }
+        }
+        Inline::Link { target, description } => render_link(target, description.as_deref(), anchors),
+        Inline::LineBreak => "\\\n".to_string(),
+    }
+}
+
+fn scheme_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*:").unwrap())
+}
+
+fn render_link(target: &str, description: Option<&[Inline]>, anchors: &Anchors) -> String {
+    let title = link_title(target);
+    let url = if let Some(title) = &title {
+        format!("#{}", anchors.find(title).map_or_else(|| heading_anchor(title), str::to_string))
+    } else if let Some(path) = target.strip_prefix("file:") {
+        path.to_string()
+    } else {
+        target.to_string()
+    };
+    let text = match description {
+        Some(description) => render_inlines(description, anchors),
+        None if scheme_re().is_match(&url) && !url.contains([' ', '<', '>']) => return format!("<{url}>"),
+        None => escape_text(title.as_deref().unwrap_or(target)),
+    };
+    if url.contains([' ', '(', ')']) {
+        format!("[{text}](<{url}>)")
+    } else {
+        format!("[{text}]({url})")
+    }
+}
+
+/// The anchor GitHub gives a heading: lower case, spaces as dashes, and
+/// punctuation other than `-` and `_` dropped.
+pub fn heading_anchor(title: &str) -> String {
+    title
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `anchor`, numbered if one of `known` already has it, as GitHub does for
+/// repeated headings.
+pub(crate) fn unique_anchor(known: &[String], anchor: String) -> String {
+    let mut unique = anchor.clone();
+    let mut suffix = 1;
+    while known.contains(&unique) {
+        unique = format!("{anchor}-{suffix}");
+        suffix += 1;
+    }
+    unique
+}
+
+/// Escapes the characters that would otherwise be read as inline markup.
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '~') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn line_start_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?m)^([ \t]*)([#>+=-]|\d+[.)])").unwrap())
+}
+
+/// Escapes paragraph lines that would otherwise start a heading, quote or
+/// list item.
+fn escape_line_starts(text: &str) -> String {
+    line_start_re()
+        .replace_all(text, |caps: &regex::Captures| {
+            let marker = &caps[2];
+            let (digits, punctuation) = marker.split_at(marker.len() - 1);
+            format!("{}{digits}\\{punctuation}", &caps[1])
+        })
+        .into_owned()
+}
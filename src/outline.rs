@@ -0,0 +1,86 @@
+use crate::heading::{heading_level, BlockTracker};
+
+/// A headline and the byte range of its whole subtree in the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeadingSpan {
+    pub level: usize,
+    /// 1-based line number of the headline.
+    pub line: usize,
+    /// Byte offset where the headline starts.
+    pub start: usize,
+    /// Byte offset just past the headline's own line.
+    pub body_start: usize,
+    /// Byte offset where the subtree ends: the next headline at the same or
+    /// a shallower level, or the end of the document.
+    pub end: usize,
+}
+
+impl HeadingSpan {
+    pub fn contains(&self, other: &HeadingSpan) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+}
+
+/// Lists every headline of `input` in document order, skipping star lines
+/// inside `#+BEGIN_...`/`#+END_...` blocks.
+pub fn heading_spans(input: &str) -> Vec<HeadingSpan> {
+    let mut spans: Vec<HeadingSpan> = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+    let mut blocks = BlockTracker::default();
+    let mut offset = 0;
+
+    for (number, line) in input.split_inclusive('\n').enumerate() {
+        let in_block = blocks.in_block(line);
+        if let Some(level) = heading_level(line).filter(|_| !in_block) {
+            while let Some(&last) = open.last() {
+                if spans[last].level < level {
+                    break;
+                }
+                spans[last].end = offset;
+                open.pop();
+            }
+            open.push(spans.len());
+            spans.push(HeadingSpan {
+                level,
+                line: number + 1,
+                start: offset,
+                body_start: offset + line.len(),
+                end: input.len(),
+            });
+        }
+        offset += line.len();
+    }
+
+    spans
+}
+
+/// Returns the indices of the ancestors of `spans[index]`, outermost first.
+pub fn ancestors(spans: &[HeadingSpan], index: usize) -> Vec<usize> {
+    let mut path = Vec::new();
+    let mut level = spans[index].level;
+    for i in (0..index).rev() {
+        if spans[i].level < level && spans[i].contains(&spans[index]) {
+            path.push(i);
+            level = spans[i].level;
+        }
+    }
+    path.reverse();
+    path
+}
+
+/// Returns the index of each span's parent headline, `None` for top-level ones.
+pub fn parent_indices(spans: &[HeadingSpan]) -> Vec<Option<usize>> {
+    let mut parents = Vec::with_capacity(spans.len());
+    let mut open: Vec<usize> = Vec::new();
+    for (index, span) in spans.iter().enumerate() {
+        while open
+            .last()
+            .is_some_and(|&last| spans[last].level >= span.level)
+        {
+            open.pop();
+        }
+        parents.push(open.last().copied());
+        open.push(index);
+    }
+    parents
+}
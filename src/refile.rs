@@ -0,0 +1,122 @@
+use crate::heading::{parse_heading, TodoKeywords};
+use crate::levels::shift_levels;
+use crate::outline::{heading_spans, parent_indices, HeadingSpan};
+use crate::section::push_group;
+use regex::Regex;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefileResult {
+    /// The source document without the refiled subtrees (and with them, when
+    /// refiling within the same document).
+    pub source: String,
+    /// The target document with the refiled subtrees, when it is a
+    /// different document.
+    pub target: Option<String>,
+    pub count: usize,
+}
+
+fn span_title(input: &str, span: &HeadingSpan, keywords: &TodoKeywords) -> Option<String> {
+    parse_heading(&input[span.start..span.body_start], keywords).map(|h| h.title)
+}
+
+/// Finds the headline at `path`, a `/`-separated list of headline titles
+/// from the top level down (TODO keywords, priorities and tags are ignored).
+/// Returns its index in `heading_spans(input)`.
+pub fn find_heading_path(input: &str, path: &str) -> Option<usize> {
+    let keywords = TodoKeywords::from_document(input);
+    let spans = heading_spans(input);
+    let parents = parent_indices(&spans);
+
+    let mut found = None;
+    for part in path.split('/').map(str::trim) {
+        found = Some((0..spans.len()).find(|&i| {
+            parents[i] == found && span_title(input, &spans[i], &keywords).as_deref() == Some(part)
+        })?);
+    }
+    found
+}
+
+/// Moves every subtree whose headline matches `select` to the end of the
+/// subtree at heading path `to`, adjusting levels to fit below it, like
+/// `org-refile`. The target is looked up in `target` when given, and in
+/// `source` otherwise. When matches are nested, only the outermost moves.
+pub fn refile(
+    source: &str,
+    target: Option<&str>,
+    select: &Regex,
+    to: &str,
+) -> Result<RefileResult, Box<dyn std::error::Error>> {
+    let spans = heading_spans(source);
+    let target_doc = target.unwrap_or(source);
+    let target_index = find_heading_path(target_doc, to)
+        .ok_or_else(|| format!("Heading path '{to}' not found"))?;
+    let target_span = heading_spans(target_doc)[target_index];
+
+    let mut selected: Vec<HeadingSpan> = Vec::new();
+    for span in &spans {
+        if selected.last().is_some_and(|last| last.contains(span)) {
+            continue;
+        }
+        let line = &source[span.start..span.body_start];
+        if select.is_match(line.trim_end()) {
+            if target.is_none() && span.contains(&target_span) {
+                return Err(format!(
+                    "Cannot refile '{}' (line {}) into itself",
+                    line.trim_end(),
+                    span.line
+                )
+                .into());
+            }
+            selected.push(*span);
+        }
+    }
+
+    if selected.is_empty() {
+        return Ok(RefileResult {
+            source: source.to_string(),
+            target: target.map(str::to_string),
+            count: 0,
+        });
+    }
+
+    let mut remaining = String::with_capacity(source.len());
+    let mut moved = String::new();
+    let mut kept_until = 0;
+    for span in &selected {
+        remaining.push_str(&source[kept_until..span.start]);
+        let delta = (target_span.level + 1) as isize - span.level as isize;
+        push_group(
+            &mut moved,
+            &shift_levels(&source[span.start..span.end], delta)?,
+        );
+        kept_until = span.end;
+    }
+    remaining.push_str(&source[kept_until..]);
+
+    let insert_into = |doc: &str| -> Result<String, Box<dyn std::error::Error>> {
+        let index =
+            find_heading_path(doc, to).ok_or_else(|| format!("Heading path '{to}' not found"))?;
+        let end = heading_spans(doc)[index].end;
+        let mut output = String::with_capacity(doc.len() + moved.len());
+        output.push_str(&doc[..end]);
+        push_group(&mut output, &moved);
+        if end < doc.len() && !output.ends_with('\n') {
+            output.push('\n');
+        }
+        output.push_str(&doc[end..]);
+        Ok(output)
+    };
+
+    Ok(match target {
+        Some(target) => RefileResult {
+            source: remaining,
+            target: Some(insert_into(target)?),
+            count: selected.len(),
+        },
+        None => RefileResult {
+            source: insert_into(&remaining)?,
+            target: None,
+            count: selected.len(),
+        },
+    })
+}
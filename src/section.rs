@@ -0,0 +1,169 @@
+use crate::heading::{heading_level, parse_heading, Heading, TodoKeywords};
+use regex::Regex;
+use std::sync::OnceLock;
+
+fn timestamp_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"[<\[](?P<date>\d{4}-\d{2}-\d{2})(?:\s+[A-Za-z]+\.?)?(?:\s+(?P<time>\d{1,2}:\d{2}))?[^>\]\n]*[>\]]",
+        )
+        .unwrap()
+    })
+}
+
+/// Appends a group to a reassembled document, terminating the previous group
+/// first if it ended without a newline, so that headlines stay on their own
+/// lines after reordering.
+pub(crate) fn push_group(output: &mut String, group: &str) {
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+    output.push_str(group);
+}
+
+/// Parses the headline on the first line of a group, if it has one.
+pub fn group_heading(group: &str, keywords: &TodoKeywords) -> Option<Heading> {
+    let first = group.split_inclusive('\n').next()?;
+    parse_heading(first, keywords)
+}
+
+/// Lines of the group's first section after the headline, up to the first
+/// child headline.
+fn section_body(group: &str) -> impl Iterator<Item = &str> {
+    let mut lines = group.split_inclusive('\n').peekable();
+    if lines
+        .peek()
+        .is_some_and(|line| heading_level(line).is_some())
+    {
+        lines.next();
+    }
+    lines.take_while(|line| heading_level(line).is_none())
+}
+
+/// Looks up `NAME` in the `:PROPERTIES:` drawer of the group's first section.
+/// Property names are matched case-insensitively, as in org.
+pub fn property(group: &str, name: &str) -> Option<String> {
+    let mut in_drawer = false;
+    for line in section_body(group) {
+        let trimmed = line.trim();
+        if !in_drawer {
+            in_drawer = trimmed.eq_ignore_ascii_case(":PROPERTIES:");
+            continue;
+        }
+        if trimmed.eq_ignore_ascii_case(":END:") {
+            break;
+        }
+        let Some(rest) = trimmed.strip_prefix(':') else {
+            continue;
+        };
+        let Some((key, value)) = rest.split_once(':') else {
+            continue;
+        };
+        if key.trim_end_matches('+').eq_ignore_ascii_case(name) {
+            return Some(value.trim().to_string());
+        }
+    }
+    None
+}
+
+/// Finds a planning keyword such as `DEADLINE`, `SCHEDULED` or `CLOSED` in
+/// the group's first section and returns its timestamp text.
+pub fn planning_timestamp(group: &str, keyword: &str) -> Option<String> {
+    let marker = format!("{keyword}:");
+    for line in section_body(group) {
+        let Some(at) = line.find(&marker) else {
+            continue;
+        };
+        let rest = &line[at + marker.len()..];
+        if let Some(m) = timestamp_re().find(rest) {
+            return Some(m.as_str().to_string());
+        }
+    }
+    None
+}
+
+/// Reduces an org timestamp like `<2024-05-01 Wed 10:00>` to a sortable
+/// `2024-05-01 10:00` key. Returns `None` for text without a timestamp.
+pub fn timestamp_key(text: &str) -> Option<String> {
+    let caps = timestamp_re().captures(text)?;
+    let date = &caps["date"];
+    Some(match caps.name("time") {
+        Some(time) => format!("{date} {:0>5}", time.as_str()),
+        None => date.to_string(),
+    })
+}
+
+/// Returns the 1-based line number each group starts on in the document the
+/// groups were split from.
+pub fn group_line_numbers<S: AsRef<str>>(groups: &[S]) -> Vec<usize> {
+    let mut line = 1;
+    groups
+        .iter()
+        .map(|group| {
+            let start = line;
+            line += group.as_ref().matches('\n').count();
+            start
+        })
+        .collect()
+}
+
+fn planning_line_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^[ \t]*(?:SCHEDULED|DEADLINE|CLOSED):").unwrap())
+}
+
+/// Adds `properties` to the `:PROPERTIES:` drawer of the section headed on the
+/// first line of `subtree`, creating the drawer after the headline and its
+/// planning line if there is none.
+pub fn insert_properties(subtree: &str, properties: &[(&str, String)]) -> String {
+    let lines: Vec<&str> = subtree.split_inclusive('\n').collect();
+    let Some(first) = lines.first() else {
+        return subtree.to_string();
+    };
+
+    let mut drawer_at = 1;
+    while drawer_at < lines.len() && planning_line_re().is_match(lines[drawer_at]) {
+        drawer_at += 1;
+    }
+    let has_drawer = lines
+        .get(drawer_at)
+        .is_some_and(|line| line.trim().eq_ignore_ascii_case(":PROPERTIES:"));
+    let drawer_end = if has_drawer {
+        lines[drawer_at..]
+            .iter()
+            .position(|line| line.trim().eq_ignore_ascii_case(":END:"))
+            .map(|offset| drawer_at + offset)
+    } else {
+        None
+    };
+
+    let mut new_lines = String::new();
+    for (key, value) in properties {
+        new_lines.push_str(&format!(":{key}: {value}\n"));
+    }
+
+    let mut output = String::with_capacity(subtree.len() + new_lines.len() + 32);
+    output.push_str(first);
+    if !first.ends_with('\n') {
+        output.push('\n');
+    }
+    match drawer_end {
+        Some(end) => {
+            output.push_str(&lines[1..end].concat());
+            output.push_str(&new_lines);
+            output.push_str(&lines[end..].concat());
+        }
+        None => {
+            output.push_str(&lines[1..drawer_at].concat());
+            if !output.ends_with('\n') {
+                output.push('\n');
+            }
+            output.push_str(":PROPERTIES:\n");
+            output.push_str(&new_lines);
+            output.push_str(":END:\n");
+            output.push_str(&lines[drawer_at..].concat());
+        }
+    }
+    output
+}
@@ -0,0 +1,167 @@
+use std::io;
+use std::process::ExitStatus;
+#[cfg(unix)]
+use std::sync::{Arc, Mutex, PoisonError};
+#[cfg(unix)]
+use std::time::Duration;
+
+/// Exit status of a process killed by `SIGPIPE`, as reported by shells.
+pub const EXIT_BROKEN_PIPE: i32 = 128 + 13;
+
+/// Exit status when a command is stopped for running too long, as with
+/// `timeout(1)`.
+pub const EXIT_TIMED_OUT: i32 = 124;
+
+/// The exit code a shell would report for `status`: the child's own code,
+/// or `128 + signo` when it was killed by a signal.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// Whether `error` is, or wraps, a failed write to a closed pipe.
+pub fn is_broken_pipe(error: &(dyn std::error::Error + 'static)) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+/// A child process to signal, or the process group it leads when `group`
+/// is set. Every signal is sent under a lock that [`ChildTarget::stop`]
+/// also takes, so once it returns none is in flight and the child may be
+/// reaped without risking a signal to a process that reuses its pid.
+#[cfg(unix)]
+#[derive(Clone)]
+pub struct ChildTarget {
+    pid: libc::pid_t,
+    group: bool,
+    stopped: Arc<Mutex<bool>>,
+}
+
+#[cfg(unix)]
+impl ChildTarget {
+    pub fn new(pid: u32, group: bool) -> io::Result<Self> {
+        let pid = libc::pid_t::try_from(pid).map_err(io::Error::other)?;
+        Ok(ChildTarget {
+            pid,
+            group,
+            stopped: Arc::new(Mutex::new(false)),
+        })
+    }
+
+    /// Sends `signal`, unless signalling has been stopped. Returns whether
+    /// it was sent.
+    fn send(&self, signal: libc::c_int) -> bool {
+        let stopped = self.stopped.lock().unwrap_or_else(PoisonError::into_inner);
+        if *stopped {
+            return false;
+        }
+        let target = if self.group { -self.pid } else { self.pid };
+        // SAFETY: kill(2) has no memory-safety preconditions. The child has
+        // not been reaped while we hold the lock, so its pid is still ours.
+        unsafe {
+            libc::kill(target, signal);
+        }
+        true
+    }
+
+    /// Blocks until the child has exited, but leaves it unreaped so that
+    /// its pid cannot be reused yet.
+    pub fn wait_for_exit(&self) -> io::Result<()> {
+        let pid = libc::id_t::try_from(self.pid).map_err(io::Error::other)?;
+        loop {
+            // SAFETY: an all-zero siginfo_t is valid, and waitid(2) only
+            // writes to it.
+            let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+            // SAFETY: `info` outlives the call.
+            if unsafe { libc::waitid(libc::P_PID, pid, &mut info, libc::WEXITED | libc::WNOWAIT) } == 0 {
+                return Ok(());
+            }
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+    }
+
+    /// Stops all signalling to the child. Call it before reaping the child.
+    pub fn stop(&self) {
+        *self.stopped.lock().unwrap_or_else(PoisonError::into_inner) = true;
+    }
+}
+
+/// Relays `SIGINT`, `SIGTERM`, `SIGHUP`, `SIGQUIT`, `SIGUSR1` and `SIGUSR2`
+/// to a child process (or its whole process group) for as long as it lives,
+/// instead of letting them terminate us first. Forwarding stops when
+/// the target is stopped or this is dropped.
+#[cfg(unix)]
+pub struct SignalForwarder {
+    handle: signal_hook::iterator::Handle,
+}
+
+#[cfg(unix)]
+impl SignalForwarder {
+    pub fn new(target: ChildTarget) -> io::Result<Self> {
+        use signal_hook::consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGUSR1, SIGUSR2};
+
+        let mut signals =
+            signal_hook::iterator::Signals::new([SIGINT, SIGTERM, SIGHUP, SIGQUIT, SIGUSR1, SIGUSR2])?;
+        let handle = signals.handle();
+        std::thread::spawn(move || {
+            for signal in signals.forever() {
+                target.send(signal);
+            }
+        });
+        Ok(SignalForwarder { handle })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for SignalForwarder {
+    fn drop(&mut self) {
+        self.handle.close();
+    }
+}
+
+/// Terminates a child process that runs past its deadline: `SIGTERM` first,
+/// then `SIGKILL` if it is still running `kill_after` later.
+#[cfg(unix)]
+pub struct Watchdog {
+    done: std::sync::mpsc::Sender<()>,
+    thread: std::thread::JoinHandle<bool>,
+}
+
+#[cfg(unix)]
+impl Watchdog {
+    pub fn new(target: ChildTarget, timeout: Duration, kill_after: Duration) -> io::Result<Self> {
+        use std::sync::mpsc::{channel, RecvTimeoutError};
+
+        let (done, finished) = channel::<()>();
+        let thread = std::thread::spawn(move || {
+            if finished.recv_timeout(timeout) != Err(RecvTimeoutError::Timeout) {
+                return false;
+            }
+            if !target.send(libc::SIGTERM) {
+                return false;
+            }
+            if finished.recv_timeout(kill_after) == Err(RecvTimeoutError::Timeout) {
+                target.send(libc::SIGKILL);
+            }
+            true
+        });
+        Ok(Watchdog { done, thread })
+    }
+
+    /// Stops watching, once the child has exited. Returns whether it was
+    /// terminated for running too long.
+    pub fn finish(self) -> bool {
+        let _ = self.done.send(());
+        self.thread.join().unwrap_or(false)
+    }
+}
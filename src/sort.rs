@@ -0,0 +1,175 @@
+use crate::group_org_sections;
+use crate::heading::{heading_level, TodoKeywords};
+use crate::section::{group_heading, planning_timestamp, property, push_group, timestamp_key};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// What to order sibling sections by, mirroring the keys of `org-sort`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortKey {
+    Heading,
+    Todo,
+    Priority,
+    Deadline,
+    Property(String),
+    Created,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "heading" => Ok(SortKey::Heading),
+            "todo" => Ok(SortKey::Todo),
+            "priority" => Ok(SortKey::Priority),
+            "deadline" => Ok(SortKey::Deadline),
+            "created" => Ok(SortKey::Created),
+            _ => match s.strip_prefix("property:") {
+                Some(name) if !name.is_empty() => Ok(SortKey::Property(name.to_string())),
+                _ => Err(format!(
+                    "Invalid sort key '{s}' (expected heading, todo, priority, deadline, property:NAME or created)"
+                )),
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum SortValue {
+    Rank(usize),
+    Text(String),
+}
+
+impl Ord for SortValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SortValue::Rank(a), SortValue::Rank(b)) => a.cmp(b),
+            (SortValue::Text(a), SortValue::Text(b)) => natural_cmp(a, b),
+            (SortValue::Rank(_), SortValue::Text(_)) => Ordering::Less,
+            (SortValue::Text(_), SortValue::Rank(_)) => Ordering::Greater,
+        }
+    }
+}
+
+impl PartialOrd for SortValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compares strings with embedded digit runs by numeric value, so that
+/// `0:30 < 1:00 < 10:00` and `item2 < item10`.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |it: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(d) = it.peek().copied().filter(char::is_ascii_digit) {
+                        digits.push(d);
+                        it.next();
+                    }
+                    digits.trim_start_matches('0').to_string()
+                };
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn sort_value(group: &str, key: &SortKey, keywords: &TodoKeywords) -> Option<SortValue> {
+    match key {
+        SortKey::Heading => {
+            group_heading(group, keywords).map(|h| SortValue::Text(h.title.to_lowercase()))
+        }
+        SortKey::Todo => {
+            let keyword = group_heading(group, keywords)?.keyword?;
+            keywords
+                .active
+                .iter()
+                .chain(&keywords.done)
+                .position(|k| *k == keyword)
+                .map(SortValue::Rank)
+        }
+        SortKey::Priority => {
+            // Headlines without a cookie have org's default priority, B.
+            let heading = group_heading(group, keywords)?;
+            Some(SortValue::Rank(heading.priority.unwrap_or('B') as usize))
+        }
+        SortKey::Deadline => planning_timestamp(group, "DEADLINE")
+            .and_then(|ts| timestamp_key(&ts))
+            .map(SortValue::Text),
+        SortKey::Property(name) => property(group, name).map(SortValue::Text),
+        SortKey::Created => {
+            let created = property(group, "CREATED")?;
+            Some(SortValue::Text(timestamp_key(&created).unwrap_or(created)))
+        }
+    }
+}
+
+/// Reorders sibling sections at `level` by `key`, like `org-sort`.
+///
+/// Only consecutive groups headed at exactly `level` are reordered among
+/// themselves; the preamble and shallower parent headlines stay in place,
+/// and each group is moved with its whole subtree. The sort is stable, and
+/// groups without a value for the key always sort last.
+pub fn sort_sections(
+    input: &str,
+    level: usize,
+    key: &SortKey,
+    reverse: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let keywords = TodoKeywords::from_document(input);
+    let groups = group_org_sections(input, level)?;
+
+    let mut output = String::with_capacity(input.len());
+    let mut run: Vec<(Option<SortValue>, String)> = Vec::new();
+
+    let flush = |run: &mut Vec<(Option<SortValue>, String)>, output: &mut String| {
+        run.sort_by(|(a, _), (b, _)| match (a, b) {
+            (Some(a), Some(b)) if reverse => b.cmp(a),
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        for (_, group) in run.drain(..) {
+            push_group(output, &group);
+        }
+    };
+
+    for group in groups {
+        if heading_level(&group) == Some(level) {
+            run.push((sort_value(&group, key, &keywords), group));
+        } else {
+            flush(&mut run, &mut output);
+            push_group(&mut output, &group);
+        }
+    }
+    flush(&mut run, &mut output);
+
+    // A final line without a newline may have moved up; keep the document's
+    // original ending.
+    if !input.ends_with('\n') && output.ends_with('\n') {
+        output.pop();
+    }
+    Ok(output)
+}
@@ -0,0 +1,113 @@
+use crate::heading::{heading_level, TodoKeywords};
+use crate::levels::promote_to_top;
+use crate::section::group_line_numbers;
+use crate::template::{render_template, SectionFields};
+use std::collections::HashSet;
+
+pub const DEFAULT_SPLIT_TEMPLATE: &str = "{index:03}-{slug}.org";
+
+#[derive(Debug, Clone)]
+pub struct SplitOptions {
+    /// File name template, see [`render_template`].
+    pub template: String,
+    /// Renumber stars so that each file starts at level 1.
+    pub promote: bool,
+    /// Copy the text before the first headline into every file.
+    pub copy_preamble: bool,
+}
+
+impl Default for SplitOptions {
+    fn default() -> Self {
+        SplitOptions {
+            template: DEFAULT_SPLIT_TEMPLATE.to_string(),
+            promote: false,
+            copy_preamble: false,
+        }
+    }
+}
+
+/// One output file of [`split_groups`], with a path relative to the target
+/// directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// Returns the text before the first headline of `input`.
+pub fn preamble(input: &str) -> &str {
+    let mut offset = 0;
+    for line in input.split_inclusive('\n') {
+        if heading_level(line).is_some() {
+            break;
+        }
+        offset += line.len();
+    }
+    &input[..offset]
+}
+
+/// Plans one file per headed group. Groups without a headline (the preamble)
+/// do not get a file of their own; use `copy_preamble` to keep that text.
+///
+/// Placeholder values are sanitized so they cannot introduce path
+/// separators, and two groups mapping to the same path is an error.
+pub fn split_groups<S: AsRef<str>>(
+    groups: &[S],
+    preamble: &str,
+    keywords: &TodoKeywords,
+    options: &SplitOptions,
+) -> Result<Vec<SplitFile>, Box<dyn std::error::Error>> {
+    let lines = group_line_numbers(groups);
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+
+    let headed = groups
+        .iter()
+        .zip(lines)
+        .filter(|(group, _)| heading_level(group.as_ref()).is_some());
+
+    for (index, (group, line)) in headed.enumerate() {
+        let group = group.as_ref();
+        let fields = SectionFields::new(group, index + 1, line, keywords);
+        let path = render_template(&options.template, &fields, sanitize_path_component)?;
+        if path.is_empty() || path.split('/').any(|part| part.is_empty() || part == "..") {
+            return Err(
+                format!("Invalid file name '{path}' for the section at line {line}").into(),
+            );
+        }
+        if !seen.insert(path.clone()) {
+            return Err(format!(
+                "Sections map to the same file '{path}' (second one at line {line}); include {{index}} in the name template"
+            )
+            .into());
+        }
+
+        let mut content = String::new();
+        if options.copy_preamble {
+            content.push_str(preamble);
+        }
+        if options.promote {
+            content.push_str(&promote_to_top(group)?);
+        } else {
+            content.push_str(group);
+        }
+        files.push(SplitFile { path, content });
+    }
+
+    Ok(files)
+}
+
+fn sanitize_path_component(value: &str) -> String {
+    let cleaned: String = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | '\0' => '-',
+            c => c,
+        })
+        .collect();
+    if cleaned == "." || cleaned == ".." {
+        cleaned.replace('.', "-")
+    } else {
+        cleaned
+    }
+}
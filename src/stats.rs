@@ -0,0 +1,59 @@
+use crate::checkbox::{subtree_checkbox_stats, CheckboxStats};
+use crate::heading::{parse_heading, TodoKeywords};
+use crate::outline::heading_spans;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentStats {
+    pub groups: usize,
+    /// Number of headlines at each level, level 1 first.
+    pub headings_per_level: Vec<usize>,
+    /// Number of headlines with each TODO keyword, in `#+TODO` order.
+    pub keywords: Vec<(String, usize)>,
+    pub checkboxes: CheckboxStats,
+}
+
+impl DocumentStats {
+    pub fn headings(&self) -> usize {
+        self.headings_per_level.iter().sum()
+    }
+}
+
+/// Counts the groups, their headlines (outside blocks), the TODO keywords
+/// of those headlines and their checkboxes.
+pub fn document_stats<S: AsRef<str>>(groups: &[S], keywords: &TodoKeywords) -> DocumentStats {
+    let mut stats = DocumentStats {
+        groups: groups.len(),
+        keywords: keywords
+            .active
+            .iter()
+            .chain(&keywords.done)
+            .map(|keyword| (keyword.clone(), 0))
+            .collect(),
+        ..DocumentStats::default()
+    };
+
+    for group in groups {
+        let group = group.as_ref();
+        for span in heading_spans(group) {
+            if stats.headings_per_level.len() < span.level {
+                stats.headings_per_level.resize(span.level, 0);
+            }
+            stats.headings_per_level[span.level - 1] += 1;
+
+            let keyword = parse_heading(&group[span.start..span.body_start], keywords)
+                .and_then(|heading| heading.keyword);
+            if let Some((_, count)) = stats
+                .keywords
+                .iter_mut()
+                .find(|(k, _)| Some(k) == keyword.as_ref())
+            {
+                *count += 1;
+            }
+        }
+        let checkboxes = subtree_checkbox_stats(group);
+        stats.checkboxes.checked += checkboxes.checked;
+        stats.checkboxes.total += checkboxes.total;
+    }
+
+    stats
+}
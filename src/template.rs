@@ -0,0 +1,216 @@
+use crate::heading::{Heading, TodoKeywords};
+use crate::section::{group_heading, property};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Metadata of one group, as exposed to `{name}` placeholders.
+pub struct SectionFields<'a> {
+    pub index: usize,
+    pub line: usize,
+    pub group: &'a str,
+    heading: Option<Heading>,
+    /// Whether known placeholders without a value expand to nothing.
+    empty_when_missing: bool,
+}
+
+impl<'a> SectionFields<'a> {
+    /// `index` is the 1-based position of the section among those processed,
+    /// 0 for the text before the first heading, and `line` the line it
+    /// starts on in the input.
+    pub fn new(group: &'a str, index: usize, line: usize, keywords: &TodoKeywords) -> Self {
+        SectionFields {
+            index,
+            line,
+            group,
+            heading: group_heading(group, keywords),
+            empty_when_missing: false,
+        }
+    }
+
+    /// Lets known placeholders without a value, like `{title}` for the text
+    /// before the first headline, expand to nothing instead of failing.
+    pub fn empty_when_missing(mut self) -> Self {
+        self.empty_when_missing = true;
+        self
+    }
+
+    /// Whether `name` is one of the placeholders of [`SectionFields::get`].
+    pub fn is_known(name: &str) -> bool {
+        matches!(
+            name,
+            "index" | "line" | "level" | "heading" | "title" | "slug" | "todo" | "priority" | "tags"
+        ) || name.strip_prefix("property:").is_some_and(|key| !key.is_empty())
+    }
+
+    /// Resolves a placeholder name: `index`, `line`, `level`, `heading`,
+    /// `title`, `slug`, `todo`, `priority`, `tags` or `property:NAME`.
+    pub fn get(&self, name: &str) -> Option<String> {
+        let value = self.value(name);
+        if value.is_none() && self.empty_when_missing && Self::is_known(name) {
+            return Some(String::new());
+        }
+        value
+    }
+
+    fn value(&self, name: &str) -> Option<String> {
+        let heading = self.heading.as_ref();
+        match name {
+            "index" => Some(self.index.to_string()),
+            "line" => Some(self.line.to_string()),
+            "level" => heading.map(|h| h.level.to_string()),
+            "heading" => self.group.lines().next().map(|l| l.trim_end().to_string()),
+            "title" => heading.map(|h| h.title.clone()),
+            "slug" => Some(slugify(heading.map_or("", |h| h.title.as_str()))),
+            "todo" => Some(heading.and_then(|h| h.keyword.clone()).unwrap_or_default()),
+            "priority" => Some(
+                heading
+                    .and_then(|h| h.priority)
+                    .map(String::from)
+                    .unwrap_or_default(),
+            ),
+            "tags" => Some(heading.map(|h| h.tags.join(":")).unwrap_or_default()),
+            _ => {
+                let key = name.strip_prefix("property:")?;
+                property(self.group, key)
+            }
+        }
+    }
+}
+
+/// Lowercases `title` and joins its alphanumeric runs with dashes.
+pub fn slugify(title: &str) -> String {
+    let slug = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+/// Quotes `value` for a POSIX shell, leaving it bare when that is safe.
+pub fn shell_quote(value: &str) -> String {
+    let safe = |b: u8| b.is_ascii_alphanumeric() || b"-_./=:@,+%".contains(&b);
+    if !value.is_empty() && value.bytes().all(safe) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// Expands `{name}` and `{name:WIDTH}` placeholders from `fields`, where a
+/// width like `03` zero-pads and `3` space-pads the value. `{{` and `}}`
+/// produce literal braces. Every substituted value is passed through
+/// `escape` first.
+pub fn render_template(
+    template: &str,
+    fields: &SectionFields,
+    escape: impl Fn(&str) -> String,
+) -> Result<String, String> {
+    let mut output = String::new();
+    let mut rest = template;
+
+    while let Some(at) = rest.find(['{', '}']) {
+        output.push_str(&rest[..at]);
+        let tail = &rest[at..];
+        if let Some(after) = tail.strip_prefix("{{") {
+            output.push('{');
+            rest = after;
+            continue;
+        }
+        if let Some(after) = tail.strip_prefix("}}") {
+            output.push('}');
+            rest = after;
+            continue;
+        }
+        if tail.starts_with('}') {
+            return Err(format!("Unmatched '}}' in template '{template}'"));
+        }
+        let end = tail
+            .find('}')
+            .ok_or_else(|| format!("Unclosed '{{' in template '{template}'"))?;
+        let placeholder = &tail[1..end];
+        output.push_str(&expand(placeholder, fields, &escape)?);
+        rest = &tail[end + 1..];
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+fn known_placeholder_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\{(?P<placeholder>[a-z]+(?::[^{}]*)?)\}").unwrap())
+}
+
+/// Expands the placeholders of [`render_template`] whose name is known,
+/// leaving everything else as it is, braces included. For the arguments of
+/// a command, which may hold braces of their own, like an awk program.
+pub fn render_known_placeholders(
+    template: &str,
+    fields: &SectionFields,
+    escape: impl Fn(&str) -> String,
+) -> Result<String, String> {
+    let mut output = String::new();
+    let mut last = 0;
+    for caps in known_placeholder_re().captures_iter(template) {
+        let placeholder = &caps["placeholder"];
+        let name = placeholder.rsplit_once(':').map_or(placeholder, |(name, width)| {
+            if !width.is_empty() && width.bytes().all(|b| b.is_ascii_digit()) {
+                name
+            } else {
+                placeholder
+            }
+        });
+        if !SectionFields::is_known(name) {
+            continue;
+        }
+        let whole = caps.get(0).unwrap();
+        output.push_str(&template[last..whole.start()]);
+        output.push_str(&expand(placeholder, fields, &escape)?);
+        last = whole.end();
+    }
+    output.push_str(&template[last..]);
+    Ok(output)
+}
+
+fn expand(
+    placeholder: &str,
+    fields: &SectionFields,
+    escape: &impl Fn(&str) -> String,
+) -> Result<String, String> {
+    // `property:NAME` contains a colon of its own, so the width is the last
+    // segment only when it is numeric.
+    let (name, width) = match placeholder.rsplit_once(':') {
+        Some((name, width)) if !width.is_empty() && width.bytes().all(|b| b.is_ascii_digit()) => {
+            (name, Some(width))
+        }
+        _ => (placeholder, None),
+    };
+
+    let value = fields.get(name).ok_or_else(|| {
+        format!(
+            "Placeholder '{{{placeholder}}}' is unknown or has no value for the section at line {}",
+            fields.line
+        )
+    })?;
+    let value = escape(&value);
+
+    Ok(match width {
+        Some(width) => {
+            let size: usize = width
+                .parse()
+                .map_err(|_| format!("Invalid width in '{{{placeholder}}}'"))?;
+            if width.starts_with('0') {
+                format!("{value:0>size$}")
+            } else {
+                format!("{value:>size$}")
+            }
+        }
+        None => value,
+    })
+}
@@ -1,8 +1,18 @@
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use std::process::Command;
 use std::fs;
 use tempfile::NamedTempFile;
 use std::io::Write;
 
+/// A command for `program` that ignores the developer's own config files,
+/// whose defaults would change what these tests expect.
+fn command(program: &str) -> Command {
+    let mut command = Command::new(program);
+    command.env("ORG_GROUPER_NO_CONFIG", "1");
+    command
+}
+
 #[test]
 fn test_basic_grouping_with_cat() {
     let org_content = "* First\nContent 1\n* Second\nContent 2\n";
@@ -10,7 +20,7 @@ fn test_basic_grouping_with_cat() {
     temp_file.write_all(org_content.as_bytes()).unwrap();
     temp_file.flush().unwrap();
 
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "--group-headings-at=1", "cat"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -30,7 +40,7 @@ fn test_level_2_grouping() {
     temp_file.write_all(org_content.as_bytes()).unwrap();
     temp_file.flush().unwrap();
 
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "--group-headings-at=2", "cat"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -52,7 +62,7 @@ fn test_no_null_replacement() {
     temp_file.write_all(org_content.as_bytes()).unwrap();
     temp_file.flush().unwrap();
 
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "--out-replace-nulls=no", "cat"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -71,7 +81,7 @@ fn test_with_grep_command() {
     temp_file.flush().unwrap();
 
     // Use ugrep with --null-data to find sections containing "TODO"
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "ugrep", "--null-data", "TODO"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -93,7 +103,7 @@ fn test_error_handling_invalid_level() {
     temp_file.write_all(org_content.as_bytes()).unwrap();
     temp_file.flush().unwrap();
 
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "--group-headings-at=invalid", "cat"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -107,7 +117,7 @@ fn test_empty_input() {
     let temp_file = NamedTempFile::new().unwrap();
     // Empty file
 
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "cat"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -127,7 +137,7 @@ fn test_complex_nested_structure() {
     temp_file.flush().unwrap();
 
     // Test level 1 grouping
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "--group-headings-at=1", "wc", "-l"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -136,7 +146,7 @@ fn test_complex_nested_structure() {
     assert!(output.status.success());
     
     // Test level 2 grouping  
-    let output2 = Command::new("cargo")
+    let output2 = command("cargo")
         .args(&["run", "--", "--group-headings-at=2", "wc", "-l"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -153,7 +163,7 @@ fn test_custom_null_replacement() {
     temp_file.flush().unwrap();
 
     // Test with pipe separator
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "--out-replace-nulls-with=| ", "cat"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -170,7 +180,7 @@ fn test_tab_replacement() {
     temp_file.write_all(org_content.as_bytes()).unwrap();
     temp_file.flush().unwrap();
 
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "--out-replace-nulls-with=\\t", "cat"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -188,7 +198,7 @@ fn test_empty_replacement() {
     temp_file.write_all(org_content.as_bytes()).unwrap();
     temp_file.flush().unwrap();
 
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "--out-replace-nulls-with=", "cat"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -206,7 +216,7 @@ fn test_level_3_includes_all_higher_levels() {
     temp_file.write_all(org_content.as_bytes()).unwrap();
     temp_file.flush().unwrap();
 
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "--group-headings-at=3", "cat"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -231,7 +241,7 @@ fn test_double_dash_separator() {
     temp_file.flush().unwrap();
 
     // Test that -- works to separate our options from command options
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "--group-headings-at=1", "--", "echo", "test-output"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -249,7 +259,7 @@ fn test_command_with_conflicting_option_names() {
     temp_file.flush().unwrap();
 
     // Test passing a command that has its own --help option
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "--", "sh", "-c", "echo 'command executed'"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -267,7 +277,7 @@ fn test_hyphen_values_in_command() {
     temp_file.flush().unwrap();
 
     // Test that we can pass values starting with hyphens to commands
-    let output = Command::new("cargo")
+    let output = command("cargo")
         .args(&["run", "--", "echo", "-n", "no-newline"])
         .stdin(std::fs::File::open(temp_file.path()).unwrap())
         .output()
@@ -277,4 +287,888 @@ fn test_hyphen_values_in_command() {
     // echo -n should not add a newline, so content should be concatenated
     assert!(stdout.contains("no-newline"));
     assert!(!stdout.ends_with("\n\n")); // Should not have extra newline from echo -n
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_incomplete_checkboxes_filter() {
+    let org_content = "* Open\n- [ ] todo\n* Finished\n- [X] done\n* Plain\nText\n";
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(org_content.as_bytes()).unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "--incomplete-checkboxes", "cat"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "* Open\n- [ ] todo\n");
+}
+
+#[test]
+fn test_update_cookies_without_command() {
+    let org_content = "Intro\n* Tasks [0/0]\n- [X] one\n- [ ] two\n";
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(org_content.as_bytes()).unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "--update-cookies"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "Intro\n* Tasks [1/2]\n- [X] one\n- [ ] two\n");
+}
+
+#[test]
+fn test_sort_by_without_command() {
+    let org_content = "Preamble\n* TODO Second\n** Detail\n* Alpha\n";
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(org_content.as_bytes()).unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "--sort-by", "heading"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "Preamble\n* Alpha\n* TODO Second\n** Detail\n");
+}
+
+#[test]
+fn test_dedupe_report() {
+    let org_content = "* Inbox\nItem\n* Other\n* Inbox\nItem\n";
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(org_content.as_bytes()).unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "--dedupe=exact", "--dedupe-report"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "lines 1, 4: * Inbox\n");
+}
+
+#[test]
+fn test_dedupe_report_lines_match_input() {
+    let org_content = "* Inbox [0/1]\n- [X] Item\n* Other\n* Inbox [0/1]\n- [X] Item\n";
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(org_content.as_bytes()).unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command(env!("CARGO_BIN_EXE_org_grouper"))
+        .args(["--dedupe=exact", "--dedupe-report", "--update-cookies"])
+        .arg("--input")
+        .arg(temp_file.path())
+        .arg("--no-config")
+        .stdin(std::process::Stdio::null())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "lines 1, 4: * Inbox [1/1]\n");
+
+    // Sorting moves groups away from the lines they were read from.
+    let output = command(env!("CARGO_BIN_EXE_org_grouper"))
+        .args(["--dedupe=exact", "--dedupe-report", "--sort-by=heading"])
+        .arg("--input")
+        .arg(temp_file.path())
+        .arg("--no-config")
+        .stdin(std::process::Stdio::null())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_split_into_directory() {
+    let org_content = "#+TITLE: Notes\n* Alpha\n** Inner\n* Beta\n";
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(org_content.as_bytes()).unwrap();
+    temp_file.flush().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let out_dir = dir.path().join("notes");
+
+    let output = command("cargo")
+        .args(["run", "--", "--split-into", out_dir.to_str().unwrap(), "--copy-preamble"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(out_dir.join("001-alpha.org")).unwrap(),
+        "#+TITLE: Notes\n* Alpha\n** Inner\n"
+    );
+    assert_eq!(
+        fs::read_to_string(out_dir.join("002-beta.org")).unwrap(),
+        "#+TITLE: Notes\n* Beta\n"
+    );
+
+    // `list` numbers the sections as `split` names their files.
+    let output = command("cargo")
+        .args(["run", "--", "list", "--template={index:03}-{slug}.org"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "001-alpha.org\n002-beta.org\n");
+
+    // A second run must not overwrite the files.
+    let output = command("cargo")
+        .args(["run", "--", "--split-into", out_dir.to_str().unwrap()])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_join_subcommand() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = dir.path().join("inbox.org");
+    let second = dir.path().join("work.org");
+    fs::write(&first, "#+TITLE: Captures\n* Idea\n").unwrap();
+    fs::write(&second, "* Meeting\n** Notes\n").unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "join", first.to_str().unwrap(), second.to_str().unwrap()])
+        .args(["--under-heading", "Archive"])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout,
+        "* Archive\n** Captures\n*** Idea\n** work\n*** Meeting\n**** Notes\n"
+    );
+}
+
+#[test]
+fn test_shift_levels_with_select() {
+    let org_content = "* Projects\n** Alpha\n*** Notes\n** Beta\n";
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(org_content.as_bytes()).unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "--group-headings-at=2", "--shift-levels=-1", "--select", "Alpha"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "* Projects\n* Alpha\n** Notes\n** Beta\n");
+
+    let output = command("cargo")
+        .args(["run", "--", "--shift-levels=-1"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_archive_subcommand() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("todo.org");
+    fs::write(&file, "* TODO Keep\n* DONE Finished\nCLOSED: [2000-01-01 Sat]\n").unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "archive", file.to_str().unwrap(), "--older-than", "30"])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert!(output.status.success());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "* TODO Keep\n");
+    let archive = fs::read_to_string(dir.path().join("todo.org_archive")).unwrap();
+    assert!(archive.starts_with("#    -*- mode: org -*-\n"));
+    assert!(archive.contains("* DONE Finished\nCLOSED: [2000-01-01 Sat]\n:PROPERTIES:\n:ARCHIVE_TIME: "));
+    assert!(archive.contains(":ARCHIVE_TODO: DONE\n:END:\n"));
+}
+
+#[test]
+fn test_refile_dry_run_prints_diff() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("notes.org");
+    let original = "* Inbox\n** Idea\n* Projects\n** Backlog\n";
+    fs::write(&file, original).unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "refile", file.to_str().unwrap()])
+        .args(["--select", "Idea", "--to", "Projects/Backlog", "--dry-run"])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("-** Idea\n"));
+    assert!(stdout.contains("+*** Idea\n"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), original);
+
+    let output = command("cargo")
+        .args(["run", "--", "refile", file.to_str().unwrap()])
+        .args(["--select", "Idea", "--to", "Projects/Backlog"])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "* Inbox\n* Projects\n** Backlog\n*** Idea\n"
+    );
+
+    // Naming FILE again as the target file, by another path, refiles in place.
+    fs::write(&file, original).unwrap();
+    let same_file = dir.path().join(".").join("notes.org");
+    let output = command(env!("CARGO_BIN_EXE_org_grouper"))
+        .args(["refile", file.to_str().unwrap()])
+        .args(["--select", "Idea", "--to", "Projects/Backlog", "--target-file", same_file.to_str().unwrap()])
+        .arg("--no-config")
+        .stdin(std::process::Stdio::null())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "* Inbox\n* Projects\n** Backlog\n*** Idea\n"
+    );
+}
+
+#[test]
+fn test_diff_for_rewriting_options() {
+    let org_content = "* Tasks [0/1]\n- [X] done\n";
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(org_content.as_bytes()).unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "--update-cookies", "--diff"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("--- a/<stdin>\n+++ b/<stdin>\n"));
+    assert!(stdout.contains("-* Tasks [0/1]\n+* Tasks [1/1]\n"));
+}
+
+#[test]
+fn test_archive_diff_leaves_files_untouched() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("todo.org");
+    let original = "* TODO Keep\n* DONE Finished\n";
+    fs::write(&file, original).unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "archive", file.to_str().unwrap(), "--diff"])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("-* DONE Finished\n"));
+    assert!(stdout.contains("todo.org_archive\n"));
+    assert!(stdout.contains("+:ARCHIVE_TODO: DONE\n"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), original);
+    assert!(!dir.path().join("todo.org_archive").exists());
+}
+
+#[test]
+fn test_nul_in_input_needs_framing() {
+    let org_content = "* One\nnul \0 here\n* Two\n";
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(org_content.as_bytes()).unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "cat"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("NUL byte at line 2"));
+
+    let output = command("cargo")
+        .args(["run", "--", "--framing=escape", "--out-replace-nulls-with=|", "cat"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"* One\nnul \0 here\n|* Two\n");
+}
+
+#[test]
+fn test_netstring_framing_output() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(b"* A\n* B\n").unwrap();
+    temp_file.flush().unwrap();
+    let run = |args: &[&str]| {
+        command(env!("CARGO_BIN_EXE_org_grouper"))
+            .args(args)
+            .stdin(std::fs::File::open(temp_file.path()).unwrap())
+            .output()
+            .expect("Failed to execute org_grouper")
+    };
+
+    // Most commands do not answer in netstrings: their output passes through.
+    let output = run(&["--framing=netstring", "wc", "-c"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "14");
+    let output = run(&["--framing=netstring", "--sh", "cat >/dev/null; echo plain; exit 3"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "plain\n");
+
+    // Decoding them is asked for explicitly.
+    let output = run(&["--framing=netstring", "--out-replace-nulls=yes", "--out-replace-nulls-with=|", "cat"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "* A\n|* B\n|");
+}
+
+#[test]
+fn test_in_and_out_separators() {
+    let org_content = "* One\ntext\n* Two\n";
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(org_content.as_bytes()).unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "--in-separator=\\n--\\n", "--out-replace-nulls-with=|", "cat"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "* One\ntext\n|* Two\n");
+
+    let output = command("cargo")
+        .args(["run", "--", "--in-separator=@@", "--out-separator=\\n", "--out-replace-nulls-with=;"])
+        .args(["awk", "BEGIN { RS = \"@@\" } { print NR }"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1;2;");
+
+    // An empty separator is rejected up front rather than panicking.
+    let output = command(env!("CARGO_BIN_EXE_org_grouper"))
+        .args(["--no-config", "--out-separator=", "cat"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--out-separator"));
+}
+
+#[test]
+fn test_large_output_does_not_deadlock() {
+    // Far more than a pipe buffer, so `cat` blocks on its stdout while we
+    // are still writing its stdin unless both happen concurrently.
+    let org_content = "* Section\nsome body text\n".repeat(50_000);
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(org_content.as_bytes()).unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "cat"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout.len(), org_content.len() + 49_999);
+}
+
+#[test]
+fn test_stderr_controls() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(b"* Section\n").unwrap();
+    temp_file.flush().unwrap();
+
+    let script = "cat >/dev/null; printf 'a\\0b\\nc\\n' >&2; printf 'x\\0y'";
+    let output = command("cargo")
+        .args(["run", "--", "--err-replace-nulls=no", "--err-prefix=[cmd] ", "sh", "-c", script])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"x\ny");
+    assert!(output.stderr.ends_with(b"[cmd] a\0b\n[cmd] c\n"));
+
+    // The prefix names the group CMD is being fed. The first group is
+    // larger than a pipe buffer, so CMD's first line comes while it is fed.
+    let input = format!("* First\n{}* Second\n", "x\n".repeat(1 << 20));
+    let mut child = command(env!("CARGO_BIN_EXE_org_grouper"))
+        .args(["--no-config", "--err-prefix={line} {title}: ", "--sh"])
+        .arg("echo start >&2; sleep 0.3; cat >/dev/null; echo end >&2")
+        .stdin(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to execute org_grouper");
+    let mut stdin = child.stdin.take().unwrap();
+    std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("1 First: start\n{} Second: end\n", (1 << 20) + 2)
+    );
+}
+
+#[test]
+fn test_exit_code_of_signalled_child() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(b"* Section\n").unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "sh", "-c", "cat >/dev/null; kill -TERM $$"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert_eq!(output.status.code(), Some(128 + 15));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_signals_are_forwarded_to_child() {
+    let mut child = command(env!("CARGO_BIN_EXE_org_grouper"))
+        .args(["sh", "-c", "cat >/dev/null; exec sleep 30"])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .expect("Failed to execute org_grouper");
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    let killed = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(128 + 2));
+}
+
+#[test]
+fn test_broken_stdout_pipe_is_quiet() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file
+        .write_all("* Section\nbody\n".repeat(100_000).as_bytes())
+        .unwrap();
+    temp_file.flush().unwrap();
+
+    let mut child = command(env!("CARGO_BIN_EXE_org_grouper"))
+        .arg("cat")
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to execute org_grouper");
+    let mut first = [0; 1];
+    std::io::Read::read_exact(child.stdout.as_mut().unwrap(), &mut first).unwrap();
+    drop(child.stdout.take());
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(128 + 13));
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_sh_runs_script_through_shell() {
+    let org_content = "* TODO One\n* Two\n* TODO Three\n";
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(org_content.as_bytes()).unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "--sh", "tr '\\0' '\\n' | grep -c TODO"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n");
+}
+
+#[test]
+fn test_each_substitutes_quoted_placeholders() {
+    let org_content = "* Bob's $(touch pwned) task\nbody\n* Second\n";
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(org_content.as_bytes()).unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "--each", "--sh", "printf '%s:%s:' {index} {title}; wc -l"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().map(str::trim).collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("1:Bob's $(touch pwned) task:"), "{stdout}");
+    assert!(lines[0].ends_with('2'), "{stdout}");
+    assert!(lines[1].starts_with("2:Second:"), "{stdout}");
+
+    let output = command("cargo")
+        .args(["run", "--", "--each", "--", "echo", "{line}", "{title}"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "1 Bob's $(touch pwned) task\n3 Second\n"
+    );
+
+    // The preamble has no title, and braces that are not placeholders are
+    // passed through.
+    let output = command(env!("CARGO_BIN_EXE_org_grouper"))
+        .args(["--no-config", "--each", "--sh", "echo {index} \"<\"{title}\">\""])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child.stdin.take().unwrap().write_all(b"Intro\n* A\n")?;
+            child.wait_with_output()
+        })
+        .expect("Failed to execute org_grouper");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0 <>\n1 <A>\n");
+
+    let output = command(env!("CARGO_BIN_EXE_org_grouper"))
+        .args(["--no-config", "--each", "awk", "{print NR \":\" $0}"])
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "1:* Bob's $(touch pwned) task\n2:body\n1:* Second\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_timeout_escalates_to_sigkill() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(b"* Section\n").unwrap();
+    temp_file.flush().unwrap();
+
+    let started = std::time::Instant::now();
+    let output = command("cargo")
+        .args(["run", "--", "--timeout=500ms", "--kill-after=500ms", "sh", "-c"])
+        .arg("trap '' TERM; cat >/dev/null; while :; do sleep 0.1; done")
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert_eq!(output.status.code(), Some(124));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("timed out"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(20));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_resource_limits() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(b"* Section\n").unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command("cargo")
+        .args(["run", "--", "--limit-memory=64M", "--limit-cpu=5", "sh", "-c"])
+        .arg("cat >/dev/null; ulimit -v; ulimit -t")
+        .stdin(std::fs::File::open(temp_file.path()).unwrap())
+        .output()
+        .expect("Failed to execute org_grouper");
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "65536\n5\n");
+}
+
+#[test]
+fn test_config_defaults_and_aliases() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join(".org_grouper.toml"),
+        "[defaults]\ngroup-headings-at = 2\nout-replace-nulls-with = '|'\n\n[aliases]\ncount = ['--sh', 'tr -cd \"\\000\" | wc -c']\n",
+    )
+    .unwrap();
+    let org_content = "* Top\n** One\n** Two\n";
+    let run = |args: &[&str]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_org_grouper"))
+            .args(args)
+            .env_remove("ORG_GROUPER_NO_CONFIG")
+            .current_dir(dir.path())
+            .env("XDG_CONFIG_HOME", dir.path().join("xdg"))
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .expect("Failed to execute org_grouper");
+        child.stdin.take().unwrap().write_all(org_content.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let output = run(&["cat"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "* Top\n|** One\n|** Two\n");
+
+    // Options on the command line override the defaults.
+    let output = run(&["--group-headings-at=1", "cat"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "* Top\n** One\n** Two\n");
+
+    let output = run(&["@count"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "2");
+
+    let output = run(&["--no-config", "cat"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "* Top\n** One\n** Two\n");
+    let mut child = command(env!("CARGO_BIN_EXE_org_grouper"))
+        .arg("cat")
+        .current_dir(dir.path())
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to execute org_grouper");
+    child.stdin.take().unwrap().write_all(org_content.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "* Top\n** One\n** Two\n");
+
+    // Subcommands get the defaults they have an option for.
+    let output = run(&["list", "--template={level} {title}"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1 Top\n2 One\n2 Two\n");
+
+    // `list` has no --format, so it ignores the export format default.
+    fs::write(dir.path().join(".org_grouper.toml"), "[defaults]\nformat = 'markdown'\n").unwrap();
+    let output = run(&["list"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1: * Top\n");
+    let output = run(&["export"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "# Top\n\n## One\n\n## Two\n");
+}
+
+#[test]
+fn test_subcommands() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file
+        .write_all(b"#+TODO: TODO | DONE\n* TODO Write [/]\n- [ ] draft\n- [X] outline\n** Notes\n* DONE Ship\n")
+        .unwrap();
+    temp_file.flush().unwrap();
+    let path = temp_file.path().to_str().unwrap();
+    let run = |args: &[&str]| {
+        command(env!("CARGO_BIN_EXE_org_grouper"))
+            .args(args)
+            .arg("--no-config")
+            .stdin(std::process::Stdio::null())
+            .output()
+            .expect("Failed to execute org_grouper")
+    };
+
+    let output = run(&["list", "--input", path]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "2: * TODO Write [/]\n6: * DONE Ship\n"
+    );
+
+    let output = run(&["grep", "-c", "^\\* DONE", "--input", path]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    let output = run(&["grep", "nowhere", "--input", path]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+
+    let output = run(&["outline", "--input", path]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "TODO Write [/]\n  Notes\nDONE Ship\n"
+    );
+
+    let output = run(&["stats", "--input", path]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("groups: 3\n"), "{stdout}");
+    assert!(stdout.contains("checkboxes: 1/2 (50%)\n"), "{stdout}");
+
+    let output = run(&["export", "--update-cookies", "--diff", "--input", path]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("-* TODO Write [/]\n+* TODO Write [1/2]\n"), "{stdout}");
+
+    let dir = tempfile::tempdir().unwrap();
+    let output = run(&["split", dir.path().to_str().unwrap(), "--input", path]);
+    assert!(output.status.success());
+    assert!(dir.path().join("001-write.org").is_file());
+    assert!(dir.path().join("002-ship.org").is_file());
+
+    // `exec` and the bare form feed the groups to CMD the same way.
+    let exec = run(&["exec", "--input", path, "--sh", "tr -d '\\n'"]);
+    let bare = run(&["--input", path, "--sh", "tr -d '\\n'"]);
+    assert!(exec.status.success());
+    assert_eq!(exec.stdout, bare.stdout);
+    assert!(String::from_utf8_lossy(&exec.stdout).contains("\n* TODO Write"));
+}
+
+#[test]
+fn test_completions_and_man_page() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file
+        .write_all(b"#+TODO: TODO WAIT | DONE\n* Projects\n** WAIT Backlog :next:\n")
+        .unwrap();
+    temp_file.flush().unwrap();
+    let run = |args: &[&str]| {
+        command(env!("CARGO_BIN_EXE_org_grouper"))
+            .args(args)
+            .arg("--no-config")
+            .stdin(std::process::Stdio::null())
+            .output()
+            .expect("Failed to execute org_grouper")
+    };
+
+    let output = run(&["completions", "zsh", "--from", temp_file.path().to_str().unwrap()]);
+    assert!(output.status.success());
+    let script = String::from_utf8_lossy(&output.stdout);
+    assert!(script.starts_with("#compdef org_grouper"), "{script}");
+    assert!(script.contains(":LEVEL:(1 2 3 4 5 6)"), "{script}");
+    assert!(script.contains(":PATH:(Projects Projects/Backlog)"), "{script}");
+    assert!(script.contains("(TODO WAIT DONE \\:next\\:)"), "{script}");
+
+    // Completion values are only offered, never enforced.
+    let output = run(&["--group-headings-at", "9", "--sh", "cat >/dev/null"]);
+    assert!(output.status.success());
+
+    let output = run(&["completions", "bash"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("complete -F _org_grouper"));
+
+    let output = run(&["man"]);
+    let page = String::from_utf8_lossy(&output.stdout);
+    assert!(page.contains(".TH org_grouper 1"), "{page}");
+    assert!(page.contains("org_grouper\\-refile"), "{page}");
+    let output = run(&["man", "refile"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(".TH org_grouper-refile 1"));
+}
+
+#[test]
+fn test_markdown_input() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file
+        .write_all(b"# Notes\n```sh\n# not a heading\n```\nSecond\n======\n- [ ] open\n")
+        .unwrap();
+    temp_file.flush().unwrap();
+    let path = temp_file.path().to_str().unwrap();
+    let run = |args: &[&str]| {
+        command(env!("CARGO_BIN_EXE_org_grouper"))
+            .args(args)
+            .arg("--no-config")
+            .stdin(std::process::Stdio::null())
+            .output()
+            .expect("Failed to execute org_grouper")
+    };
+
+    let output = run(&["--input-format=markdown", "--input", path, "--out-replace-nulls-with=|", "--sh", "cat"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "# Notes\n```sh\n# not a heading\n```\n|Second\n======\n- [ ] open\n"
+    );
+
+    let output = run(&["grep", "--input-format=markdown", "--input", path, "open"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Second\n======\n- [ ] open\n");
+
+    let output = run(&["export", "--input-format=markdown", "--input", path, "--sort-by=heading"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--sort-by only works on org input"));
+}
+
+#[test]
+fn test_export_markdown() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file
+        .write_all(b"#+TITLE: Notes\n* TODO One :a:\n:PROPERTIES:\n:ID: 1\n:END:\n- [X] *done*\n* Two\n=x=\n")
+        .unwrap();
+    temp_file.flush().unwrap();
+    let path = temp_file.path().to_str().unwrap();
+    let run = |args: &[&str]| {
+        command(env!("CARGO_BIN_EXE_org_grouper"))
+            .args(args)
+            .arg("--no-config")
+            .stdin(std::process::Stdio::null())
+            .output()
+            .expect("Failed to execute org_grouper")
+    };
+
+    let output = run(&["export", "--format", "markdown", "--input", path]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "# TODO One :a:\n\n- [x] **done**\n\n# Two\n\n`x`\n"
+    );
+
+    let output = run(&["export", "--format=markdown", "--properties=front-matter", "--input", path]);
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("---\ntitle: \"Notes\"\nid: \"1\"\n---\n\n# TODO One"));
+
+    // Each group is converted on its own; the preamble has nothing to show.
+    let output = run(&["exec", "--format=markdown", "--input", path, "--out-replace-nulls-with=|", "--sh", "cat"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "|# TODO One :a:\n\n- [x] **done**\n|# Two\n\n`x`\n"
+    );
+
+    let output = run(&["export", "--input-format=markdown", "--format=markdown", "--input", path]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--format only converts org input"));
+}
+
+#[test]
+fn test_grep_export_html() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file
+        .write_all(b"#+TITLE: Team notes\n* Roadmap :public:\n- [X] launch\n* Salaries\n")
+        .unwrap();
+    temp_file.flush().unwrap();
+
+    let output = command(env!("CARGO_BIN_EXE_org_grouper"))
+        .args(["grep", ":public:", "--format", "html", "--input", temp_file.path().to_str().unwrap()])
+        .arg("--no-config")
+        .stdin(std::process::Stdio::null())
+        .output()
+        .expect("Failed to execute org_grouper");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // The page is named after the whole document.
+    assert!(stdout.contains("<title>Team notes</title>"));
+    assert!(stdout.contains("<section id=\"roadmap\" class=\"level-1\">"));
+    assert!(stdout.contains("<li class=\"on\"><input type=\"checkbox\" checked disabled> launch</li>"));
+    assert!(!stdout.contains("Salaries"));
+}
+
+#[test]
+fn test_heading_regex() {
+    let input = "intro\nh1. One\nh2. One.a\nh1. Two\n";
+    let mut child = command(env!("CARGO_BIN_EXE_org_grouper"))
+        .args(["--heading-regex", r"^h(?P<level>\d)\. ", "--out-replace-nulls-with=|", "--sh", "cat"])
+        .arg("--no-config")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to execute org_grouper");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "intro\n|h1. One\nh2. One.a\n|h1. Two\n"
+    );
+}
@@ -1,3 +1,39 @@
+use org_grouper::archive::{archive_done, ArchiveOptions};
+use org_grouper::checkbox::{
+    has_incomplete_checkboxes, section_checkbox_stats, subtree_checkbox_stats, update_cookies,
+    CheckboxStats,
+};
+use org_grouper::completion::CompletionValues;
+use org_grouper::config::{config_paths, load_config, Config, PROJECT_CONFIG};
+use org_grouper::detector::{
+    group_sections, HeadingDetector, InputFormat, MarkdownHeadings, RegexHeadings, RstHeadings,
+};
+use org_grouper::diff::unified_diff;
+use org_grouper::export::{export, parse_inline, ExportFormat, ExportOptions, Inline, PropertyMode};
+use org_grouper::dedupe::{content_hash, dedupe_groups, dedupe_sections, duplicate_clusters, DedupeMode};
+use org_grouper::framing::{
+    frame_groups, group_offsets, replace_separators, unframe_output, Framing, LinePrefixer, OutputTranslator,
+};
+use org_grouper::heading::{parse_heading, TodoKeywords};
+use org_grouper::join::{document_title, join_documents, JoinOptions};
+use org_grouper::levels::{promote_to_top, shift_levels, shift_sections};
+use org_grouper::limits::{parse_duration, parse_size};
+use org_grouper::markdown::heading_anchor;
+use org_grouper::outline::{ancestors, heading_spans, parent_indices};
+use org_grouper::refile::{find_heading_path, refile};
+use proptest::prelude::*;
+use regex::Regex;
+#[cfg(unix)]
+use org_grouper::signals::{ChildTarget, Watchdog};
+use org_grouper::section::{
+    group_line_numbers, insert_properties, planning_timestamp, property, timestamp_key,
+};
+use org_grouper::sort::{sort_sections, SortKey};
+use org_grouper::split::{preamble, split_groups, SplitFile, SplitOptions};
+use org_grouper::stats::document_stats;
+use org_grouper::template::{
+    render_known_placeholders, render_template, shell_quote, slugify, SectionFields,
+};
 use org_grouper::{group_org_sections, process_escape_sequences, replace_nulls_in_bytes};
 
 #[test]
@@ -79,13 +115,59 @@ fn test_level_3_grouping() {
 
 #[test]
 fn test_process_escape_sequences() {
-    assert_eq!(process_escape_sequences("\\n"), "\n");
-    assert_eq!(process_escape_sequences("\\t"), "\t");
-    assert_eq!(process_escape_sequences("\\r"), "\r");
-    assert_eq!(process_escape_sequences("\\0"), "\0");
-    assert_eq!(process_escape_sequences("\\\\"), "\\");
-    assert_eq!(process_escape_sequences("hello\\nworld"), "hello\nworld");
-    assert_eq!(process_escape_sequences("tab\\there"), "tab\there");
+    assert_eq!(process_escape_sequences("\\n").unwrap(), "\n");
+    assert_eq!(process_escape_sequences("\\t").unwrap(), "\t");
+    assert_eq!(process_escape_sequences("\\r").unwrap(), "\r");
+    assert_eq!(process_escape_sequences("\\0").unwrap(), "\0");
+    assert_eq!(process_escape_sequences("\\\\").unwrap(), "\\");
+    assert_eq!(process_escape_sequences("hello\\nworld").unwrap(), "hello\nworld");
+    assert_eq!(process_escape_sequences("tab\\there").unwrap(), "tab\there");
+}
+
+#[test]
+fn test_process_escape_sequences_single_pass() {
+    assert_eq!(process_escape_sequences("\\\\n").unwrap(), "\\n");
+    assert_eq!(process_escape_sequences("\\\\\\n").unwrap(), "\\\n");
+    assert_eq!(process_escape_sequences("\\\\0").unwrap(), "\\0");
+    assert_eq!(process_escape_sequences("").unwrap(), "");
+    assert_eq!(process_escape_sequences("plain ünïcode").unwrap(), "plain ünïcode");
+}
+
+#[test]
+fn test_process_escape_sequences_extended() {
+    assert_eq!(process_escape_sequences("\\a\\e\\f\\v").unwrap(), "\x07\x1b\x0c\x0b");
+    assert_eq!(process_escape_sequences("\\n\\x1e\\n").unwrap(), "\n\x1e\n");
+    assert_eq!(process_escape_sequences("\\x7F\\x00").unwrap(), "\x7f\0");
+    assert_eq!(process_escape_sequences("\\x41BC").unwrap(), "ABC");
+    assert_eq!(process_escape_sequences("\\u{2028}").unwrap(), "\u{2028}");
+    assert_eq!(process_escape_sequences("\\u{1F600}!").unwrap(), "\u{1F600}!");
+    assert_eq!(process_escape_sequences("\\u{e9}x").unwrap(), "éx");
+}
+
+#[test]
+fn test_process_escape_sequences_errors() {
+    for input in [
+        "\\",
+        "trailing\\",
+        "\\q",
+        "\\N",
+        "\\x",
+        "\\x1",
+        "\\xZZ",
+        "\\x80",
+        "\\u",
+        "\\u2028",
+        "\\u{}",
+        "\\u{2028",
+        "\\u{1234567}",
+        "\\u{D800}",
+        "\\u{110000}",
+        "\\u{zz}",
+    ] {
+        assert!(process_escape_sequences(input).is_err(), "{input:?} should be rejected");
+    }
+    let err = process_escape_sequences("a\\qb").unwrap_err();
+    assert!(err.contains("'\\q'"), "{err}");
 }
 
 #[test]
@@ -99,4 +181,1018 @@ fn test_replace_nulls_in_bytes() {
     
     let result = replace_nulls_in_bytes(input, "");
     assert_eq!(result, b"helloworldtest");
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_parse_heading() {
+    let keywords = TodoKeywords::default();
+    let heading = parse_heading("** TODO [#A] Write report :work:urgent:\n", &keywords).unwrap();
+    assert_eq!(heading.level, 2);
+    assert_eq!(heading.keyword.as_deref(), Some("TODO"));
+    assert_eq!(heading.priority, Some('A'));
+    assert_eq!(heading.title, "Write report");
+    assert_eq!(heading.tags, vec!["work", "urgent"]);
+
+    let plain = parse_heading("* Notes\n", &keywords).unwrap();
+    assert_eq!(plain.keyword, None);
+    assert_eq!(plain.title, "Notes");
+    assert!(parse_heading("not a heading\n", &keywords).is_none());
+}
+
+#[test]
+fn test_todo_keywords_from_document() {
+    let keywords = TodoKeywords::from_document("#+TODO: TODO(t) NEXT | DONE(d!) CANCELLED\n* NEXT Task\n");
+    assert_eq!(keywords.active, vec!["TODO", "NEXT"]);
+    assert_eq!(keywords.done, vec!["DONE", "CANCELLED"]);
+    assert!(keywords.is_done("CANCELLED"));
+
+    let implicit = TodoKeywords::from_document("#+SEQ_TODO: OPEN WAITING CLOSED\n");
+    assert_eq!(implicit.active, vec!["OPEN", "WAITING"]);
+    assert_eq!(implicit.done, vec!["CLOSED"]);
+
+    assert_eq!(TodoKeywords::from_document("* Plain\n"), TodoKeywords::default());
+}
+
+#[test]
+fn test_checkbox_stats() {
+    let group = "* Tasks\n- [X] one\n- [ ] two\n  - [x] nested\n** Child\n- [ ] three\n";
+    assert_eq!(section_checkbox_stats(group), CheckboxStats { checked: 2, total: 3 });
+    assert_eq!(subtree_checkbox_stats(group), CheckboxStats { checked: 2, total: 4 });
+    assert!(has_incomplete_checkboxes(group));
+    assert!(!has_incomplete_checkboxes("* Done\n- [X] one\n"));
+    assert!(!has_incomplete_checkboxes("* No boxes\n- plain item\n"));
+}
+
+#[test]
+fn test_checkboxes_in_blocks_are_ignored() {
+    let group = "* Example\n#+begin_example\n- [ ] not a task\n#+end_example\n- [X] real\n";
+    assert_eq!(subtree_checkbox_stats(group), CheckboxStats { checked: 1, total: 1 });
+}
+
+#[test]
+fn test_update_headline_cookies() {
+    let input = "* Shopping [0/0]\n- [X] milk\n- [ ] eggs\n  - [X] brown\n- [X] bread\n";
+    assert_eq!(
+        update_cookies(input),
+        "* Shopping [2/3]\n- [X] milk\n- [ ] eggs\n  - [X] brown\n- [X] bread\n"
+    );
+
+    let percent = "* Shopping [%]\n- [X] milk\n- [ ] eggs\n- [ ] bread\n";
+    assert!(update_cookies(percent).starts_with("* Shopping [33%]\n"));
+}
+
+#[test]
+fn test_update_cookies_counts_todo_children() {
+    let input = "* Project [1/1]\n** TODO Design\n** DONE Build\n*** TODO Nested\n** Notes\n";
+    assert_eq!(
+        update_cookies(input),
+        "* Project [1/2]\n** TODO Design\n** DONE Build\n*** TODO Nested\n** Notes\n"
+    );
+}
+
+#[test]
+fn test_update_list_item_cookies() {
+    let input = "* Plan\n- [ ] Release [/]\n  - [X] tag\n  - [ ] publish\n    - [X] deep\n- [ ] Other\n";
+    assert_eq!(
+        update_cookies(input),
+        "* Plan\n- [ ] Release [1/2]\n  - [X] tag\n  - [ ] publish\n    - [X] deep\n- [ ] Other\n"
+    );
+}
+
+#[test]
+fn test_section_property_and_planning() {
+    let group = "* Task\nDEADLINE: <2024-05-01 Wed 9:30> SCHEDULED: <2024-04-20 Sat>\n:PROPERTIES:\n:Effort:   1:30\n:END:\n** Child\n:PROPERTIES:\n:ID: child\n:END:\n";
+    assert_eq!(property(group, "EFFORT").as_deref(), Some("1:30"));
+    assert_eq!(property(group, "ID"), None);
+    assert_eq!(
+        planning_timestamp(group, "DEADLINE").as_deref(),
+        Some("<2024-05-01 Wed 9:30>")
+    );
+    assert_eq!(timestamp_key("<2024-05-01 Wed 9:30>").as_deref(), Some("2024-05-01 09:30"));
+    assert_eq!(timestamp_key("[2024-04-20 Sat]").as_deref(), Some("2024-04-20"));
+}
+
+#[test]
+fn test_parse_sort_key() {
+    assert_eq!("todo".parse::<SortKey>().unwrap(), SortKey::Todo);
+    assert_eq!(
+        "property:EFFORT".parse::<SortKey>().unwrap(),
+        SortKey::Property("EFFORT".to_string())
+    );
+    assert!("property:".parse::<SortKey>().is_err());
+    assert!("size".parse::<SortKey>().is_err());
+}
+
+#[test]
+fn test_sort_by_heading_keeps_preamble_and_subtrees() {
+    let input = "#+TITLE: Notes\n* Zebra\n** Stripes\n* apple\nRed\n* Mango\n";
+    let sorted = sort_sections(input, 1, &SortKey::Heading, false).unwrap();
+    assert_eq!(sorted, "#+TITLE: Notes\n* apple\nRed\n* Mango\n* Zebra\n** Stripes\n");
+
+    let reversed = sort_sections(input, 1, &SortKey::Heading, true).unwrap();
+    assert_eq!(reversed, "#+TITLE: Notes\n* Zebra\n** Stripes\n* Mango\n* apple\nRed\n");
+}
+
+#[test]
+fn test_sort_siblings_within_parents() {
+    let input = "* B parent\n** b2\n** b1\n* A parent\n** a2\n** a1\n";
+    let sorted = sort_sections(input, 2, &SortKey::Heading, false).unwrap();
+    assert_eq!(sorted, "* B parent\n** b1\n** b2\n* A parent\n** a1\n** a2\n");
+}
+
+#[test]
+fn test_sort_by_todo_and_priority() {
+    let input = "#+TODO: TODO NEXT | DONE\n* DONE Shipped\n* Idea\n* NEXT [#C] Soon\n* TODO [#A] Now\n";
+    let by_todo = sort_sections(input, 1, &SortKey::Todo, false).unwrap();
+    assert_eq!(
+        by_todo,
+        "#+TODO: TODO NEXT | DONE\n* TODO [#A] Now\n* NEXT [#C] Soon\n* DONE Shipped\n* Idea\n"
+    );
+
+    let by_priority = sort_sections(input, 1, &SortKey::Priority, false).unwrap();
+    assert_eq!(
+        by_priority,
+        "#+TODO: TODO NEXT | DONE\n* TODO [#A] Now\n* DONE Shipped\n* Idea\n* NEXT [#C] Soon\n"
+    );
+}
+
+#[test]
+fn test_sort_by_deadline_and_property() {
+    let input = "* Late\nDEADLINE: <2024-06-01 Sat>\n* None\n* Early\nDEADLINE: <2024-05-01 Wed>\n";
+    let sorted = sort_sections(input, 1, &SortKey::Deadline, true).unwrap();
+    assert_eq!(
+        sorted,
+        "* Late\nDEADLINE: <2024-06-01 Sat>\n* Early\nDEADLINE: <2024-05-01 Wed>\n* None\n"
+    );
+
+    let input = "* Long\n:PROPERTIES:\n:EFFORT: 10:00\n:END:\n* Short\n:PROPERTIES:\n:EFFORT: 0:30\n:END:\n* Medium\n:PROPERTIES:\n:EFFORT: 2:00\n:END:\n";
+    let sorted = sort_sections(input, 1, &SortKey::Property("EFFORT".to_string()), false).unwrap();
+    let titles: Vec<&str> = sorted.lines().filter(|l| l.starts_with('*')).collect();
+    assert_eq!(titles, vec!["* Short", "* Medium", "* Long"]);
+}
+
+#[test]
+fn test_sort_final_line_without_newline() {
+    let input = "* b\n* a";
+    assert_eq!(sort_sections(input, 1, &SortKey::Heading, false).unwrap(), "* a\n* b");
+}
+
+#[test]
+fn test_group_line_numbers() {
+    let groups = vec!["Intro\n", "* A\ntext\n", "* B\n"];
+    assert_eq!(group_line_numbers(&groups), vec![1, 2, 4]);
+}
+
+#[test]
+fn test_dedupe_exact() {
+    let input = "* Note\nSame\n* Other\n* Note\nSame\n* Note\nDifferent\n";
+    assert_eq!(
+        dedupe_sections(input, 1, DedupeMode::Exact).unwrap(),
+        "* Note\nSame\n* Other\n* Note\nDifferent\n"
+    );
+    assert_eq!(
+        dedupe_sections(input, 1, DedupeMode::Heading).unwrap(),
+        "* Note\nSame\n* Other\n"
+    );
+}
+
+#[test]
+fn test_dedupe_normalized_ignores_volatile_content() {
+    let a = "* Buy milk [0/1]\n- [ ] milk\nCaptured [2024-01-01 Mon 10:00]\n";
+    let b = "* Buy milk  [1/1]\n- [X] milk\n\nCaptured [2024-02-03 Sat 11:30]\n";
+    let c = "* Buy milk\n- [X] bread\n";
+    assert_ne!(content_hash(a, DedupeMode::Exact), content_hash(b, DedupeMode::Exact));
+    assert_eq!(content_hash(a, DedupeMode::Normalized), content_hash(b, DedupeMode::Normalized));
+
+    let groups = vec![a.to_string(), c.to_string(), b.to_string()];
+    assert_eq!(duplicate_clusters(&groups, DedupeMode::Normalized), vec![vec![0, 2]]);
+    assert_eq!(
+        dedupe_groups(groups, DedupeMode::Normalized),
+        vec![a.to_string(), c.to_string()]
+    );
+}
+
+#[test]
+fn test_shift_levels() {
+    let input = "* A\n** B\n#+begin_src org\n* not a heading\n#+end_src\n";
+    assert_eq!(
+        shift_levels(input, 1).unwrap(),
+        "** A\n*** B\n#+begin_src org\n* not a heading\n#+end_src\n"
+    );
+    assert!(shift_levels(input, -1).is_err());
+    assert_eq!(promote_to_top("*** Deep\n**** Deeper\n").unwrap(), "* Deep\n** Deeper\n");
+}
+
+#[test]
+fn test_render_template() {
+    let keywords = TodoKeywords::default();
+    let group = "** TODO Write the Report! :work:\n:PROPERTIES:\n:ID: abc-123\n:END:\n";
+    let fields = SectionFields::new(group, 7, 12, &keywords);
+    let plain = |s: &str| s.to_string();
+
+    assert_eq!(
+        render_template("{index:03}-{slug}.org", &fields, plain).unwrap(),
+        "007-write-the-report.org"
+    );
+    assert_eq!(render_template("{property:ID}.org", &fields, plain).unwrap(), "abc-123.org");
+    assert_eq!(
+        render_template("{{{todo}}} {level}:{tags} @{line:4}", &fields, plain).unwrap(),
+        "{TODO} 2:work @  12"
+    );
+    assert!(render_template("{property:CUSTOM_ID}", &fields, plain).is_err());
+    assert!(render_template("{nope}", &fields, plain).is_err());
+    assert!(render_template("{index", &fields, plain).is_err());
+
+    let preamble = SectionFields::new("Intro\n", 1, 1, &keywords).empty_when_missing();
+    assert_eq!(render_template("[{title}|{tags}|{index}]", &preamble, plain).unwrap(), "[||1]");
+    assert!(render_template("{nope}", &preamble, plain).is_err());
+    assert_eq!(
+        render_known_placeholders("{print NR} {title:3} {{x}} {nope}", &fields, plain).unwrap(),
+        "{print NR} Write the Report! {{x}} {nope}"
+    );
+    assert_eq!(slugify("  Ünïcode & more  "), "ünïcode-more");
+    assert_eq!(slugify("!!!"), "section");
+}
+
+#[test]
+fn test_split_groups() {
+    let input = "#+TITLE: Notes\n** First\nBody\n** Second\n*** Child\n";
+    let groups = group_org_sections(input, 2).unwrap();
+    let keywords = TodoKeywords::default();
+    assert_eq!(preamble(input), "#+TITLE: Notes\n");
+
+    let files = split_groups(&groups, preamble(input), &keywords, &SplitOptions::default()).unwrap();
+    assert_eq!(
+        files,
+        vec![
+            SplitFile { path: "001-first.org".to_string(), content: "** First\nBody\n".to_string() },
+            SplitFile { path: "002-second.org".to_string(), content: "** Second\n*** Child\n".to_string() },
+        ]
+    );
+
+    let options = SplitOptions {
+        template: "{title}.org".to_string(),
+        promote: true,
+        copy_preamble: true,
+    };
+    let files = split_groups(&groups, preamble(input), &keywords, &options).unwrap();
+    assert_eq!(files[1].content, "#+TITLE: Notes\n* Second\n** Child\n");
+}
+
+#[test]
+fn test_split_rejects_colliding_and_unsafe_names() {
+    let groups = vec!["* Same\n", "* Same\n"];
+    let keywords = TodoKeywords::default();
+    let options = SplitOptions {
+        template: "{slug}.org".to_string(),
+        ..SplitOptions::default()
+    };
+    assert!(split_groups(&groups, "", &keywords, &options).is_err());
+
+    let options = SplitOptions {
+        template: "{title}".to_string(),
+        ..SplitOptions::default()
+    };
+    let files = split_groups(&["* ../etc/passwd\n"], "", &keywords, &options).unwrap();
+    assert_eq!(files[0].path, "..-etc-passwd");
+}
+
+#[test]
+fn test_document_title() {
+    assert_eq!(document_title("#+title:  My Notes \n* A\n").as_deref(), Some("My Notes"));
+    assert_eq!(document_title("* A\n#+TITLE: not preamble\n"), None);
+    assert_eq!(document_title("#+TITLE:\n"), None);
+}
+
+#[test]
+fn test_join_documents() {
+    let documents = vec![
+        ("inbox".to_string(), "#+TITLE: Inbox\nCaptured stuff\n* Task\n** Detail\n".to_string()),
+        ("work".to_string(), "* Meeting\n".to_string()),
+    ];
+    assert_eq!(
+        join_documents(&documents, &JoinOptions::default()).unwrap(),
+        "* Inbox\nCaptured stuff\n** Task\n*** Detail\n* work\n** Meeting\n"
+    );
+
+    let options = JoinOptions {
+        under_heading: Some("Archive".to_string()),
+        demote: 1,
+        merge_duplicates: false,
+    };
+    assert_eq!(
+        join_documents(&documents, &options).unwrap(),
+        "** Archive\n*** Inbox\nCaptured stuff\n**** Task\n***** Detail\n*** work\n**** Meeting\n"
+    );
+}
+
+#[test]
+fn test_join_merges_duplicate_titles() {
+    let documents = vec![
+        ("a".to_string(), "#+TITLE: Notes\n* One".to_string()),
+        ("b".to_string(), "#+TITLE: Notes\n* Two\n".to_string()),
+    ];
+    let options = JoinOptions {
+        merge_duplicates: true,
+        ..JoinOptions::default()
+    };
+    assert_eq!(
+        join_documents(&documents, &options).unwrap(),
+        "* Notes\n** One\n** Two\n"
+    );
+    assert_eq!(
+        join_documents(&documents, &JoinOptions::default()).unwrap(),
+        "* Notes\n** One\n* Notes\n** Two\n"
+    );
+}
+
+#[test]
+fn test_shift_selected_sections() {
+    let input = "Intro\n* Keep\n** Child\n* Move me\n** Child\n#+begin_example\n* literal\n#+end_example\n";
+    let select = Regex::new("Move").unwrap();
+    assert_eq!(
+        shift_sections(input, 1, 1, Some(&select)).unwrap(),
+        "Intro\n* Keep\n** Child\n** Move me\n*** Child\n#+begin_example\n* literal\n#+end_example\n"
+    );
+    assert_eq!(
+        shift_sections(input, 1, 1, None).unwrap(),
+        "Intro\n** Keep\n*** Child\n** Move me\n*** Child\n#+begin_example\n* literal\n#+end_example\n"
+    );
+
+    let err = shift_sections(input, 1, -1, Some(&select)).unwrap_err();
+    assert!(err.to_string().contains("line 4"));
+
+    // Grouping at 2, a selected level 1 headline still takes its children.
+    let input = "* Parent\n** Child\n*** Grandchild\n* Other\n** Kept\n";
+    let select = Regex::new("Parent|Child").unwrap();
+    assert_eq!(
+        shift_sections(input, 2, 1, Some(&select)).unwrap(),
+        "** Parent\n*** Child\n**** Grandchild\n* Other\n** Kept\n"
+    );
+    assert_eq!(
+        shift_sections(input, 2, 1, Some(&Regex::new("Kept").unwrap())).unwrap(),
+        "* Parent\n** Child\n*** Grandchild\n* Other\n*** Kept\n"
+    );
+}
+
+#[test]
+fn test_heading_spans_and_ancestors() {
+    let input = "Intro\n* A\n** B\n#+begin_src org\n* not a heading\n#+end_src\n*** C\n** D\n* E\n";
+    let spans = heading_spans(input);
+    let summary: Vec<(usize, usize, &str)> = spans
+        .iter()
+        .map(|s| (s.level, s.line, &input[s.start..s.end]))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (1, 2, "* A\n** B\n#+begin_src org\n* not a heading\n#+end_src\n*** C\n** D\n"),
+            (2, 3, "** B\n#+begin_src org\n* not a heading\n#+end_src\n*** C\n"),
+            (3, 7, "*** C\n"),
+            (2, 8, "** D\n"),
+            (1, 9, "* E\n"),
+        ]
+    );
+    assert_eq!(ancestors(&spans, 2), vec![0, 1]);
+    assert_eq!(ancestors(&spans, 3), vec![0]);
+    assert!(ancestors(&spans, 4).is_empty());
+}
+
+#[test]
+fn test_insert_properties() {
+    let props = [("ARCHIVE_TIME", "2024-05-01 Wed 10:00".to_string())];
+    assert_eq!(
+        insert_properties("* DONE Task\nCLOSED: [2024-04-01 Mon]\nBody\n", &props),
+        "* DONE Task\nCLOSED: [2024-04-01 Mon]\n:PROPERTIES:\n:ARCHIVE_TIME: 2024-05-01 Wed 10:00\n:END:\nBody\n"
+    );
+    assert_eq!(
+        insert_properties("* Task\n:PROPERTIES:\n:ID: x\n:END:\n", &props),
+        "* Task\n:PROPERTIES:\n:ID: x\n:ARCHIVE_TIME: 2024-05-01 Wed 10:00\n:END:\n"
+    );
+    assert_eq!(
+        insert_properties("* Task", &props),
+        "* Task\n:PROPERTIES:\n:ARCHIVE_TIME: 2024-05-01 Wed 10:00\n:END:\n"
+    );
+}
+
+fn archive_options(older_than_days: Option<i64>) -> ArchiveOptions {
+    ArchiveOptions {
+        older_than_days,
+        today: chrono::NaiveDate::from_ymd_opt(2024, 5, 10).unwrap(),
+        archive_time: "2024-05-10 Fri 12:00".to_string(),
+        source_file: "/notes/todo.org".to_string(),
+        category: "todo".to_string(),
+    }
+}
+
+#[test]
+fn test_archive_done_subtrees() {
+    let input = "#+TODO: TODO | DONE CANCELLED\n* Projects\n** DONE Old\nCLOSED: [2024-04-01 Mon 09:00]\n*** TODO Leftover\n** CANCELLED Recent\nCLOSED: [2024-05-08 Wed]\n** TODO Open\n* DONE Undated\n";
+    let result = archive_done(input, &archive_options(Some(7))).unwrap();
+    assert_eq!(result.count, 1);
+    assert_eq!(
+        result.remaining,
+        "#+TODO: TODO | DONE CANCELLED\n* Projects\n** CANCELLED Recent\nCLOSED: [2024-05-08 Wed]\n** TODO Open\n* DONE Undated\n"
+    );
+    assert_eq!(
+        result.archived,
+        "* DONE Old\nCLOSED: [2024-04-01 Mon 09:00]\n:PROPERTIES:\n:ARCHIVE_TIME: 2024-05-10 Fri 12:00\n:ARCHIVE_FILE: /notes/todo.org\n:ARCHIVE_OLPATH: Projects\n:ARCHIVE_CATEGORY: todo\n:ARCHIVE_TODO: DONE\n:END:\n** TODO Leftover\n"
+    );
+
+    let everything = archive_done(input, &archive_options(None)).unwrap();
+    assert_eq!(everything.count, 3);
+    assert_eq!(
+        everything.remaining,
+        "#+TODO: TODO | DONE CANCELLED\n* Projects\n** TODO Open\n"
+    );
+    assert!(everything.archived.contains("* DONE Undated\n:PROPERTIES:\n:ARCHIVE_TIME"));
+
+    // Without #+TODO, CANCELLED entries are done too; with it, only its
+    // done states are.
+    let input = "* CANCELLED Dropped\n* DONE Shipped\n* TODO Open\n";
+    let result = archive_done(input, &archive_options(None)).unwrap();
+    assert_eq!(result.count, 2);
+    assert_eq!(result.remaining, "* TODO Open\n");
+    assert!(result.archived.contains(":ARCHIVE_TODO: CANCELLED\n"));
+    let declared = format!("#+TODO: TODO | DONE\n{input}");
+    assert_eq!(archive_done(&declared, &archive_options(None)).unwrap().count, 1);
+}
+
+#[test]
+fn test_find_heading_path() {
+    let input = "* Inbox\n** Backlog\n* Projects\n** Alpha\n** TODO Backlog :work:\n*** Backlog\n";
+    let spans = heading_spans(input);
+    assert_eq!(parent_indices(&spans), vec![None, Some(0), None, Some(2), Some(2), Some(4)]);
+    assert_eq!(find_heading_path(input, "Projects/Backlog"), Some(4));
+    assert_eq!(find_heading_path(input, "Projects / Backlog / Backlog"), Some(5));
+    assert_eq!(find_heading_path(input, "Backlog"), None);
+    assert_eq!(find_heading_path(input, "Projects/Missing"), None);
+}
+
+#[test]
+fn test_refile_within_document() {
+    let input = "* Inbox\n** Idea one\n*** Detail\n** Note\n** Idea two\n* Projects\n** Backlog\n*** Existing\n* Later\n";
+    let select = Regex::new("Idea").unwrap();
+    let result = refile(input, None, &select, "Projects/Backlog").unwrap();
+    assert_eq!(result.count, 2);
+    assert_eq!(result.target, None);
+    assert_eq!(
+        result.source,
+        "* Inbox\n** Note\n* Projects\n** Backlog\n*** Existing\n*** Idea one\n**** Detail\n*** Idea two\n* Later\n"
+    );
+
+    let select = Regex::new("Projects").unwrap();
+    assert!(refile(input, None, &select, "Projects/Backlog").is_err());
+    assert!(refile(input, None, &Regex::new("Idea").unwrap(), "Nowhere").is_err());
+}
+
+#[test]
+fn test_refile_to_other_document() {
+    let source = "* Inbox\n** TODO Call Bob\n** Keep";
+    let target = "* Tasks";
+    let select = Regex::new("TODO").unwrap();
+    let result = refile(source, Some(target), &select, "Tasks").unwrap();
+    assert_eq!(result.source, "* Inbox\n** Keep");
+    assert_eq!(result.target.as_deref(), Some("* Tasks\n** TODO Call Bob\n"));
+}
+
+#[test]
+fn test_unified_diff() {
+    assert_eq!(unified_diff("a\n", "a\n", "a/x", "b/x"), "");
+    assert_eq!(
+        unified_diff("one\ntwo\n", "one\nthree\n", "a/x.org", "b/x.org"),
+        "--- a/x.org\n+++ b/x.org\n@@ -1,2 +1,2 @@\n one\n-two\n+three\n"
+    );
+}
+
+#[test]
+fn test_frame_groups() {
+    let groups = vec!["* A\n".to_string(), "* B\\\0\n".to_string()];
+    let err = frame_groups(&groups, &Framing::Nul).unwrap_err();
+    assert!(err.contains("line 2"), "{err}");
+    assert_eq!(frame_groups(&groups[..1], &Framing::Nul).unwrap(), b"* A\n");
+    assert_eq!(
+        frame_groups(&groups, &Framing::Netstring).unwrap(),
+        b"4:* A\n,6:* B\\\0\n,"
+    );
+    assert_eq!(
+        frame_groups(&groups, &Framing::Escape).unwrap(),
+        b"* A\n\0* B\\\\\\0\n"
+    );
+    assert!("bogus".parse::<Framing>().is_err());
+}
+
+#[test]
+fn test_unframe_output() {
+    assert_eq!(unframe_output(b"a\0b\0", &Framing::Nul, "|").unwrap(), b"a|b|");
+    assert_eq!(
+        unframe_output(b"a\\0\\\\x\0b", &Framing::Escape, "|").unwrap(),
+        b"a\0\\x|b"
+    );
+    assert_eq!(
+        unframe_output(b"3:a\0b,1:c,", &Framing::Netstring, "\n").unwrap(),
+        b"a\0b\nc\n"
+    );
+    assert!(unframe_output(b"3:ab,", &Framing::Netstring, "\n").is_err());
+    assert!(unframe_output(b"x:ab,", &Framing::Netstring, "\n").is_err());
+}
+
+#[test]
+fn test_separator_framing() {
+    let groups = vec!["* A\n".to_string(), "* B\n".to_string()];
+    let framing = Framing::Separator("\x1e\n".to_string());
+    assert_eq!(frame_groups(&groups, &framing).unwrap(), b"* A\n\x1e\n* B\n");
+    assert_eq!(
+        unframe_output(b"a\x1e\nb\x1e\n", &framing, "--\n").unwrap(),
+        b"a--\nb--\n"
+    );
+
+    let err = frame_groups(&groups, &Framing::Separator("B".to_string())).unwrap_err();
+    assert!(err.contains("line 2"), "{err}");
+    assert!(frame_groups(&groups, &Framing::Separator("\n\n".to_string())).is_err());
+    assert!(frame_groups(&groups, &Framing::Separator(String::new())).is_err());
+
+    assert_eq!(replace_separators(b"a;;b;;", b";;", "\n"), b"a\nb\n");
+    assert_eq!(replace_separators(b"a;b", b"", "\n"), b"a;b");
+}
+
+#[test]
+fn test_output_translator_holds_split_sequences() {
+    let mut translator = OutputTranslator::new(Framing::Separator("<>".to_string()), "|");
+    assert_eq!(translator.feed(b"a<").unwrap(), b"a");
+    assert_eq!(translator.feed(b">b<").unwrap(), b"|b");
+    assert_eq!(translator.finish().unwrap(), b"<");
+
+    let mut translator = OutputTranslator::new(Framing::Escape, "|");
+    assert_eq!(translator.feed(b"a\\").unwrap(), b"a");
+    assert_eq!(translator.feed(b"0\0").unwrap(), b"\0|");
+
+    let mut translator = OutputTranslator::new(Framing::Netstring, "|");
+    assert_eq!(translator.feed(b"3:a").unwrap(), b"a");
+    assert_eq!(translator.feed(b"bc,1").unwrap(), b"bc|");
+    assert!(translator.finish().is_err());
+
+    // An empty separator matches nothing.
+    let mut translator = OutputTranslator::new(Framing::Separator(String::new()), "|");
+    assert_eq!(translator.feed(b"a\nb").unwrap(), b"a\nb");
+    assert_eq!(translator.finish().unwrap(), b"");
+}
+
+#[test]
+fn test_line_prefixer() {
+    let mut prefixer = LinePrefixer::new("> ");
+    assert_eq!(prefixer.feed(b"one\ntw"), b"> one\n> tw");
+    assert_eq!(prefixer.feed(b"o\n"), b"o\n");
+    assert_eq!(prefixer.feed(b"\nthree"), b"> \n> three");
+    assert_eq!(prefixer.feed(b""), b"");
+    prefixer.set_prefix("# ");
+    assert_eq!(prefixer.feed(b"\nfour\n"), b"\n# four\n");
+
+    let groups = vec!["* A\\\n".to_string(), "* B\n".to_string(), String::new()];
+    for framing in [
+        Framing::Nul,
+        Framing::Escape,
+        Framing::Netstring,
+        Framing::Separator("--".to_string()),
+    ] {
+        let framed = frame_groups(&groups, &framing).unwrap();
+        let offsets = group_offsets(&groups, &framing);
+        assert_eq!(offsets[0], 0);
+        assert_eq!(&framed[offsets[1]..offsets[1] + 1], match framing {
+            Framing::Netstring => b"4",
+            _ => b"*",
+        });
+        assert!(offsets[2] <= framed.len());
+    }
+}
+
+#[test]
+fn test_shell_quote() {
+    assert_eq!(shell_quote("plain-word_1.org"), "plain-word_1.org");
+    assert_eq!(shell_quote(""), "''");
+    assert_eq!(shell_quote("two words"), "'two words'");
+    assert_eq!(shell_quote("it's $(rm -rf /)"), "'it'\\''s $(rm -rf /)'");
+
+    let keywords = TodoKeywords::default();
+    let fields = SectionFields::new("* TODO Bob's `task`\n", 1, 1, &keywords);
+    assert_eq!(
+        render_template("echo {title} > /dev/null", &fields, shell_quote).unwrap(),
+        "echo 'Bob'\\''s `task`' > /dev/null"
+    );
+}
+
+#[test]
+fn test_parse_duration() {
+    use std::time::Duration;
+    assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+    assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+    assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+    assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+    for invalid in ["", "s", "10x", "-1s", "1.2.3s", "1 s"] {
+        assert!(parse_duration(invalid).is_err(), "{invalid:?}");
+    }
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(parse_size("65536"), Ok(65536));
+    assert_eq!(parse_size("64k"), Ok(64 * 1024));
+    assert_eq!(parse_size("512M"), Ok(512 << 20));
+    assert_eq!(parse_size("2GB"), Ok(2 << 30));
+    for invalid in ["", "M", "1.5G", "10X", "99999999999T"] {
+        assert!(parse_size(invalid).is_err(), "{invalid:?}");
+    }
+}
+
+#[test]
+fn test_config_parse() {
+    let config = Config::parse(
+        "[defaults]\ngroup-headings-at = 2\nout-replace-nulls-with = '\\n---\\n'\nupdate-cookies = true\nreverse = false\n\n[aliases]\ntodos = [\"ugrep\", \"--null-data\", \"TODO\"]\n",
+    )
+    .unwrap();
+    assert_eq!(
+        config.default_args(),
+        ["--group-headings-at=2", "--out-replace-nulls-with=\\n---\\n", "--update-cookies"]
+    );
+    assert_eq!(config.aliases["todos"], ["ugrep", "--null-data", "TODO"]);
+
+    assert!(Config::parse("[other]\nx = 1\n").is_err());
+    assert!(Config::parse("[aliases]\nx = \"ugrep TODO\"\n").is_err());
+    assert!(Config::parse("[defaults]\nx = { a = 1 }\n").is_err());
+    assert!(Config::parse("not toml").is_err());
+}
+
+#[test]
+fn test_config_merge_and_aliases() {
+    let mut config = Config::parse("[defaults]\na = 1\nb = 2\n[aliases]\nx = [\"one\"]\n").unwrap();
+    config.merge(Config::parse("[defaults]\na = 3\n[aliases]\nx = [\"two\", \"-v\"]\n").unwrap());
+    assert_eq!(config.default_args(), ["--b=2", "--a=3"]);
+
+    let args = |list: &[&str]| list.iter().map(std::ffi::OsString::from).collect::<Vec<_>>();
+    assert_eq!(
+        config.expand_alias(args(&["og", "@x", "extra"])).unwrap(),
+        args(&["og", "two", "-v", "extra"])
+    );
+    assert_eq!(config.expand_alias(args(&["og", "cat"])).unwrap(), args(&["og", "cat"]));
+    assert!(config.expand_alias(args(&["og", "@missing"])).is_err());
+}
+
+#[test]
+fn test_config_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    let nested = dir.path().join("a/b");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::write(dir.path().join("a").join(PROJECT_CONFIG), "[defaults]\nreverse = true\n").unwrap();
+
+    let xdg = dir.path().join("xdg");
+    let paths = config_paths(&nested, Some(xdg.clone()), None);
+    assert_eq!(
+        paths,
+        [xdg.join("org_grouper/config.toml"), dir.path().join("a").join(PROJECT_CONFIG)]
+    );
+    let home = config_paths(&nested, None, Some(dir.path().to_path_buf()));
+    assert_eq!(home[0], dir.path().join(".config/org_grouper/config.toml"));
+
+    assert_eq!(load_config(&paths).unwrap().default_args(), ["--reverse"]);
+
+    // A checkout cannot make org_grouper run its commands.
+    std::fs::write(dir.path().join("a").join(PROJECT_CONFIG), "[defaults]\nsh = \"echo PWNED\"\n").unwrap();
+    assert!(load_config(&paths).unwrap_err().contains("'sh' runs commands"));
+    std::fs::create_dir_all(xdg.join("org_grouper")).unwrap();
+    std::fs::write(xdg.join("org_grouper/config.toml"), "[defaults]\nshell = \"/bin/bash\"\n").unwrap();
+    std::fs::remove_file(dir.path().join("a").join(PROJECT_CONFIG)).unwrap();
+    assert_eq!(load_config(&paths).unwrap().default_args(), ["--shell=/bin/bash"]);
+
+    // Nor can it redefine an alias from the user config.
+    std::fs::write(xdg.join("org_grouper/config.toml"), "[aliases]\ntodos = [\"--sort-by=todo\"]\n").unwrap();
+    std::fs::write(
+        dir.path().join("a").join(PROJECT_CONFIG),
+        "[aliases]\ntodos = [\"--\", \"echo\", \"PWNED\"]\nnotes = [\"--reverse\"]\n",
+    )
+    .unwrap();
+    assert!(load_config(&paths).unwrap_err().contains("alias 'todos' is already defined in the user config"));
+    std::fs::write(dir.path().join("a").join(PROJECT_CONFIG), "[aliases]\nnotes = [\"--reverse\"]\n").unwrap();
+    let config = load_config(&paths).unwrap();
+    assert_eq!(config.aliases["todos"], ["--sort-by=todo"]);
+    assert_eq!(config.aliases["notes"], ["--reverse"]);
+}
+
+fn org_line() -> impl Strategy<Value = String> {
+    prop_oneof![
+        ("[*]{1,4}", "[ \t]", "(TODO |DONE )?[A-Za-z ]{0,12}( :tag:)?")
+            .prop_map(|(stars, gap, title)| format!("{stars}{gap}{title}")),
+        "[A-Za-z *:#+-]{0,20}",
+        Just("#+BEGIN_SRC org".to_string()),
+        Just("#+END_SRC".to_string()),
+        Just("- [ ] item".to_string()),
+        Just("- [X] item [0/0]".to_string()),
+        Just(String::new()),
+        Just("\0".to_string()),
+    ]
+}
+
+fn org_document() -> impl Strategy<Value = String> {
+    (
+        proptest::collection::vec(org_line(), 0..30),
+        prop_oneof![Just("\n"), Just("\r\n")],
+        any::<bool>(),
+    )
+        .prop_map(|(lines, ending, final_newline)| {
+            let mut doc = lines.join(ending);
+            if final_newline && !doc.is_empty() {
+                doc.push_str(ending);
+            }
+            doc
+        })
+}
+
+proptest! {
+    #[test]
+    fn prop_grouping_round_trips_any_text(input in "(?s).{0,200}", level in 0usize..6) {
+        let groups = group_org_sections(&input, level).unwrap();
+        prop_assert_eq!(groups.concat(), input);
+        prop_assert!(groups.iter().all(|group| !group.is_empty()));
+    }
+
+    #[test]
+    fn prop_grouping_round_trips_org_documents(input in org_document(), level in 1usize..5) {
+        let groups = group_org_sections(&input, level).unwrap();
+        prop_assert_eq!(groups.concat(), input.as_str());
+        for group in groups.iter().skip(1) {
+            let stars = group.bytes().take_while(|&b| b == b'*').count();
+            prop_assert!((1..=level).contains(&stars), "group does not start at a headline: {:?}", group);
+        }
+    }
+
+    #[test]
+    fn prop_markdown_grouping_round_trips(input in "(?s)[#=`~\\- a\n]{0,200}", level in 1usize..7) {
+        let groups = group_sections(&input, level, &mut MarkdownHeadings::default());
+        prop_assert_eq!(groups.concat(), input);
+        prop_assert!(groups.iter().all(|group| !group.is_empty()));
+    }
+
+    #[test]
+    fn prop_output_translation_is_chunk_independent(
+        output in proptest::collection::vec(prop_oneof![Just(0u8), Just(b'\\'), Just(b'0'), Just(b';'), Just(b'x')], 0..64),
+        cuts in proptest::collection::vec(0usize..64, 0..6),
+        framing in prop_oneof![
+            Just(Framing::Nul),
+            Just(Framing::Escape),
+            Just(Framing::Separator(";;x".to_string())),
+        ],
+    ) {
+        let whole = unframe_output(&output, &framing, "|").unwrap();
+        let mut cuts: Vec<usize> = cuts.into_iter().map(|cut| cut.min(output.len())).collect();
+        cuts.sort();
+        let mut translator = OutputTranslator::new(framing, "|");
+        let mut chunked = Vec::new();
+        let mut start = 0;
+        for cut in cuts.into_iter().chain([output.len()]) {
+            chunked.extend(translator.feed(&output[start..cut]).unwrap());
+            start = cut;
+        }
+        chunked.extend(translator.finish().unwrap());
+        prop_assert_eq!(chunked, whole);
+    }
+
+    #[test]
+    fn prop_shift_levels_is_reversible(input in org_document(), delta in 1isize..4) {
+        let demoted = shift_levels(&input, delta).unwrap();
+        prop_assert_eq!(shift_levels(&demoted, -delta).unwrap(), input);
+    }
+
+    #[test]
+    fn prop_update_cookies_is_idempotent(input in org_document()) {
+        let updated = update_cookies(&input);
+        prop_assert_eq!(update_cookies(&updated), updated);
+    }
+}
+
+#[test]
+fn test_document_stats() {
+    let input = "#+TODO: TODO WAIT | DONE\n* TODO One\n- [X] a\n- [ ] b\n** WAIT Nested\n#+begin_src org\n** Not a headline\n#+end_src\n* DONE Two\n** Child\n*** Deep\n";
+    let groups = group_org_sections(input, 1).unwrap();
+    let stats = document_stats(&groups, &TodoKeywords::from_document(input));
+
+    assert_eq!(stats.groups, 3);
+    assert_eq!(stats.headings_per_level, [2, 2, 1]);
+    assert_eq!(stats.headings(), 5);
+    assert_eq!(
+        stats.keywords,
+        [("TODO".to_string(), 1), ("WAIT".to_string(), 1), ("DONE".to_string(), 1)]
+    );
+    assert_eq!(stats.checkboxes, CheckboxStats { checked: 1, total: 2 });
+}
+
+#[test]
+fn test_completion_values() {
+    let input = "#+TODO: TODO WAIT | DONE\n* Projects :work:\n** TODO Backlog :next:work:\n** Either/or\n*** Hidden\n* Inbox\n#+begin_src org\n* Not a headline :code:\n#+end_src\n* Projects\n";
+    let values = CompletionValues::from_document(input);
+
+    assert_eq!(values.heading_paths, ["Projects", "Projects/Backlog", "Inbox"]);
+    assert_eq!(values.tags, ["next", "work"]);
+    assert_eq!(values.keywords, ["TODO", "WAIT", "DONE"]);
+}
+
+#[test]
+fn test_markdown_grouping() {
+    let input = "Intro\n# One\n```sh\n# comment\n```\n## One.a\n~~~\n```\n# still code\n~~~\nTwo\n===\ntext\n\n---\nThree\n---\n- item\n---\n#hashtag\n####### seven\n    # indented code\n";
+    let mut detector = InputFormat::Markdown.detector();
+    let groups = group_sections(input, 1, detector.as_mut());
+    assert_eq!(
+        groups,
+        [
+            "Intro\n",
+            "# One\n```sh\n# comment\n```\n## One.a\n~~~\n```\n# still code\n~~~\n",
+            "Two\n===\ntext\n\n---\nThree\n---\n- item\n---\n#hashtag\n####### seven\n    # indented code\n",
+        ]
+    );
+
+    let groups = group_sections(input, 2, &mut MarkdownHeadings::default());
+    let starts: Vec<&str> = groups.iter().map(|g| g.lines().next().unwrap()).collect();
+    assert_eq!(starts, ["Intro", "# One", "## One.a", "Two", "Three"]);
+}
+
+fn heading_levels(input: &str, detector: &mut dyn HeadingDetector) -> Vec<Option<usize>> {
+    let lines: Vec<&str> = input.lines().collect();
+    (0..lines.len())
+        .map(|i| detector.heading_level(lines[i], &lines[i + 1..]))
+        .collect()
+}
+
+#[test]
+fn test_rst_levels_follow_adornment_order() {
+    let input = "Title\n=====\n\n-----\n Part\n-----\ntext\n\n----\n\nSub\n===\nToo long\n--\n";
+    assert_eq!(
+        heading_levels(input, &mut RstHeadings::default()),
+        [Some(1), None, None, Some(2), None, None, None, None, None, None, Some(1), None, None, None]
+    );
+
+    // An overline needs an underline of the same style.
+    let input = "=====\nTitle\n-----\n\n-----\nPart\n-----\n";
+    assert_eq!(
+        heading_levels(input, &mut RstHeadings::default()),
+        [None, Some(1), None, None, Some(2), None, None]
+    );
+}
+
+#[test]
+fn test_asciidoc_and_outline_levels() {
+    let input = "= Doc\n== A\n----\n== listing\n----\n=== A.1\n====\n==no space\n";
+    let mut detector = InputFormat::AsciiDoc.detector();
+    assert_eq!(
+        heading_levels(input, detector.as_mut()),
+        [Some(0), Some(1), None, None, None, Some(2), None, None]
+    );
+
+    let input = ";;; demo.el --- Demo\n;;;###autoload\n;;;; Helpers\n;; comment\n";
+    let mut detector = InputFormat::Outline.detector();
+    assert_eq!(heading_levels(input, detector.as_mut()), [Some(1), None, Some(2), None]);
+}
+
+#[test]
+fn test_regex_headings() {
+    let mut levels = RegexHeadings::new(r"^h(?P<level>\d)\. ").unwrap();
+    assert_eq!(heading_levels("h2. Title\nh1.x\n", &mut levels), [Some(2), None]);
+
+    let mut stars = RegexHeadings::new(r"^(?P<stars>-+)>").unwrap();
+    assert_eq!(heading_levels("--> a\n> b\n", &mut stars), [Some(2), None]);
+
+    assert!(RegexHeadings::new("^#+ ").unwrap_err().contains("'level' or 'stars'"));
+    assert!(RegexHeadings::new("(").is_err());
+}
+
+#[test]
+fn test_parse_inline() {
+    assert_eq!(
+        parse_inline("a *b /c/* =d*e= [[https://x.org][f]]"),
+        [
+            Inline::Text("a ".to_string()),
+            Inline::Bold(vec![
+                Inline::Text("b ".to_string()),
+                Inline::Italic(vec![Inline::Text("c".to_string())]),
+            ]),
+            Inline::Text(" ".to_string()),
+            Inline::Code("d*e".to_string()),
+            Inline::Text(" ".to_string()),
+            Inline::Link {
+                target: "https://x.org".to_string(),
+                description: Some(vec![Inline::Text("f".to_string())]),
+            },
+        ]
+    );
+    // Markers need whitespace or punctuation around them.
+    assert_eq!(parse_inline("2*3*4"), [Inline::Text("2*3*4".to_string())]);
+}
+
+#[test]
+fn test_markdown_export() {
+    let input = "#+TITLE: Notes\n:PROPERTIES:\n:ID: 42\n:END:\n* TODO [#B] Plan :work:\nSCHEDULED: <2024-05-01 Wed>\n\
+                 1. one\n   - [X] done\n- term :: text\n#+begin_src sh\necho *\n#+end_src\n| a | b |\n|---+---|\n| 1 | 2 |\n";
+    let keywords = TodoKeywords::from_document(input);
+    let markdown = export(input, ExportFormat::Markdown, &keywords, &ExportOptions::default());
+    assert_eq!(
+        markdown,
+        "# TODO \\[#B\\] Plan :work:\n\n1. one\n   - [x] done\n\n- **term**: text\n\n\
+         ```sh\necho *\n```\n\n| a | b |\n| --- | --- |\n| 1 | 2 |\n"
+    );
+
+    let options = ExportOptions {
+        properties: PropertyMode::FrontMatter,
+        ..ExportOptions::default()
+    };
+    let markdown = export(input, ExportFormat::Markdown, &keywords, &options);
+    assert!(markdown.starts_with("---\ntitle: \"Notes\"\nid: \"42\"\n---\n\n# TODO"));
+    assert_eq!(export(input, ExportFormat::Org, &keywords, &options), input);
+
+    assert_eq!(heading_anchor("Next steps: 2024!"), "next-steps-2024");
+
+    // Links point at the anchor of the whole rendered heading.
+    let input = "* TODO [#A] Plan :work:\n[[*Plan]] [[*Notes]]\n* Notes\n* Notes\n";
+    let markdown = export(input, ExportFormat::Markdown, &keywords, &ExportOptions::default());
+    assert!(markdown.contains("[Plan](#todo-a-plan-work) [Notes](#notes)"), "{markdown}");
+    // Repeated anchors are numbered.
+    let input = "* A\n* A\n* A-1\n[[*A-1]]\n";
+    let markdown = export(input, ExportFormat::Markdown, &keywords, &ExportOptions::default());
+    assert!(markdown.contains("[A-1](#a-1-1)"), "{markdown}");
+    // Link targets are org text: their markup does not stop the match.
+    let input = "* TODO [#A] Meeting *bold* /it/ :work:\n[[*Meeting *bold* /it/]] [[*Gone =x=]]\n";
+    let markdown = export(input, ExportFormat::Markdown, &keywords, &ExportOptions::default());
+    assert!(
+        markdown.contains("[Meeting bold it](#todo-a-meeting-bold-it-work) [Gone x](#gone-x)"),
+        "{markdown}"
+    );
+    assert!("pdf".parse::<ExportFormat>().unwrap_err().contains("expected org, markdown or html"));
+}
+
+#[test]
+fn test_html_export() {
+    let input = "* TODO [#A] Plan :team:\n:PROPERTIES:\n:CUSTOM_ID: plan\n:END:\n\
+                 See [[*Plan]] & [[#plan][this]].\n** Steps\n- [ ] one\n#+begin_src rust\nfn main() {}\n#+end_src\n\
+                 * Plan\n";
+    let keywords = TodoKeywords::from_document(input);
+    let options = ExportOptions {
+        title: Some("Notes".to_string()),
+        ..ExportOptions::default()
+    };
+    let html = export(input, ExportFormat::Html, &keywords, &options);
+
+    assert!(html.starts_with("<!DOCTYPE html>\n"));
+    assert!(html.contains("<title>Notes</title>"));
+    assert!(html.contains(
+        "<ul>\n<li><a href=\"#plan\">Plan</a>\n<ul>\n<li><a href=\"#steps\">Steps</a></li>\n</ul>\n</li>\n\
+         <li><a href=\"#plan-1\">Plan</a></li>\n</ul>\n"
+    ));
+    assert!(html.contains(
+        "<section id=\"plan\" class=\"level-1\">\n<details open>\n<summary><h2>\
+         <span class=\"badge todo\">TODO</span> <span class=\"badge priority\">#A</span> Plan \
+         <span class=\"tags\"><span class=\"badge tag\">team</span></span></h2></summary>\n"
+    ));
+    assert!(html.contains("<p>See <a href=\"#plan\">Plan</a> &amp; <a href=\"#plan\">this</a>.</p>"));
+    assert!(html.contains("<li class=\"off\"><input type=\"checkbox\" disabled> one</li>"));
+    assert!(html.contains("<pre><code class=\"language-rust\">fn main() {}\n</code></pre>"));
+    // The level 2 section closes inside the first level 1 section.
+    assert!(html.contains("</details>\n</section>\n</details>\n</section>\n<section id=\"plan-1\""));
+    assert!(html.ends_with("</main>\n</body>\n</html>\n"));
+
+    // Link targets are org text: their markup does not stop the match.
+    let input = "* Use =code= and /this/\n[[*Use =code= and /this/]] [[*Gone *x*]]\n";
+    let html = export(input, ExportFormat::Html, &keywords, &ExportOptions::default());
+    assert!(
+        html.contains("<a href=\"#use-code-and-this\">Use code and this</a> <a href=\"#gone-x\">Gone x</a>"),
+        "{html}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_stopped_child_target_is_not_signalled() {
+    let mut child = std::process::Command::new("sleep").arg("0.1").spawn().unwrap();
+    let target = ChildTarget::new(child.id(), false).unwrap();
+    let watchdog = Watchdog::new(
+        target.clone(),
+        std::time::Duration::from_secs(10),
+        std::time::Duration::from_millis(10),
+    )
+    .unwrap();
+    target.wait_for_exit().unwrap();
+    target.stop();
+    // The child exited on its own and is still left to reap.
+    assert!(!watchdog.finish());
+    assert!(child.wait().unwrap().success());
+
+    let mut child = std::process::Command::new("sleep").arg("0.5").spawn().unwrap();
+    let target = ChildTarget::new(child.id(), false).unwrap();
+    // A watchdog that fires after the target was stopped sends nothing.
+    target.stop();
+    let watchdog = Watchdog::new(
+        target,
+        std::time::Duration::from_millis(10),
+        std::time::Duration::from_millis(10),
+    )
+    .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    assert!(!watchdog.finish());
+    assert!(child.wait().unwrap().success());
+}